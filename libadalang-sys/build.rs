@@ -31,7 +31,16 @@ impl ParseCallbacks for ItemNameCallback {
     fn add_derives(&self, info: &bindgen::callbacks::DeriveInfo<'_>) -> Vec<String> {
         if info.kind == bindgen::callbacks::TypeKind::Enum {
             vec!["Copy".into()]
-        } else if ["ada_source_location", "ada_source_location_range"].contains(&info.name) {
+        } else if [
+            "ada_source_location",
+            "ada_source_location_range",
+            "ada_internal_metadata",
+            "ada_internal_entity_info",
+            "ada_base_entity",
+            "ada_symbol_type",
+        ]
+        .contains(&info.name)
+        {
             vec!["Clone".into(), "Copy".into()]
         } else {
             vec![]
@@ -90,5 +99,318 @@ fn main() -> Result<(), Box<dyn Error>> {
     let out_path = PathBuf::from(env::var("OUT_DIR")?);
     bindings.write_to_file(out_path.join("bindings.rs"))?;
 
+    let functions = parse_functions(&bindings.to_string());
+    std::fs::write(
+        out_path.join("node_fields.rs"),
+        node_fields_table(&functions),
+    )?;
+    std::fs::write(
+        out_path.join("node_properties.rs"),
+        node_properties_table(&functions),
+    )?;
+
+    let common_spec = format!("{lal_dir}/src/libadalang-common.ads");
+    println!("cargo:rerun-if-changed={common_spec}");
+    // without the abstract node types, kind checks would silently give wrong answers
+    let common_spec = std::fs::read_to_string(&common_spec)
+        .map_err(|e| format!("cannot read {common_spec} for the abstract node types: {e}"))?;
+    std::fs::write(
+        out_path.join("node_types.rs"),
+        node_types_table(&bindings.to_string(), &common_spec)?,
+    )?;
+
     Ok(())
 }
+
+/// A function declaration parsed from the generated bindings.
+struct Function {
+    name: String,
+    /// Names and types of the parameters, with normalized whitespace.
+    params: Vec<(String, String)>,
+    return_type: String,
+}
+
+/// Normalize the whitespace of a type, which depends on whether the bindings were formatted.
+fn normalize_type(ty: &str) -> String {
+    ty.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("* mut", "*mut")
+        .replace("* const", "*const")
+        .replace(":: ", "::")
+        .replace(" ::", "::")
+}
+
+/// Parse all the function declarations of the generated bindings.
+fn parse_functions(bindings: &str) -> Vec<Function> {
+    let mut functions = Vec::new();
+
+    for decl in bindings.split("pub fn ").skip(1) {
+        let Some((decl, _)) = decl.split_once(';') else {
+            continue;
+        };
+        let Some((name, rest)) = decl.split_once('(') else {
+            continue;
+        };
+        let Some((params, return_type)) = rest.rsplit_once(')') else {
+            continue;
+        };
+
+        let params = params
+            .split(',')
+            .filter_map(|param| param.split_once(':'))
+            .map(|(name, ty)| (name.trim().to_owned(), normalize_type(ty)))
+            .collect();
+        let return_type = return_type.trim().trim_start_matches("->");
+
+        functions.push(Function {
+            name: name.trim().to_owned(),
+            params,
+            return_type: normalize_type(return_type),
+        });
+    }
+
+    functions
+}
+
+/// Split a node member accessor name, e.g. `ada_call_expr_f_name`, into its node type and
+/// member name, e.g. `call_expr` and `f_name`.
+fn split_member<'a>(name: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let name = name.strip_prefix("ada_")?;
+    let idx = name.find(&format!("_{marker}_"))?;
+    Some((&name[..idx], &name[idx + 1..]))
+}
+
+/// Generate a table of all the syntax field accessors found in the bindings.
+///
+/// Syntax fields are exposed as `ada_<node_type>_f_<field_name>` functions, which all share the
+/// same signature.
+fn node_fields_table(functions: &[Function]) -> String {
+    let mut table = String::from("pub static ada_node_fields: &[ada_node_field] = &[\n");
+
+    for func in functions {
+        let name = &func.name;
+        let Some((node_type, field)) = split_member(name, "f") else {
+            continue;
+        };
+        if node_type.contains("_p_") || !func.return_type.ends_with("c_int") {
+            // a property with `_f_` in its name
+            continue;
+        }
+
+        table.push_str(&format!(
+            "    ada_node_field {{ node_type: \"{node_type}\", name: \"{field}\", accessor: {name} }},\n"
+        ));
+    }
+
+    table.push_str("];\n");
+    table
+}
+
+/// Generate a table of all the properties found in the bindings.
+///
+/// Properties are exposed as `ada_<node_type>_p_<property_name>` functions, taking the node,
+/// the property arguments and a pointer to the result. Since their signatures differ, each one is
+/// wrapped in a thunk taking its arguments as an array of pointers.
+fn node_properties_table(functions: &[Function]) -> String {
+    let mut thunks = String::new();
+    let mut table = String::from("pub static ada_node_properties: &[ada_node_property] = &[\n");
+
+    for func in functions {
+        let name = &func.name;
+        let Some((node_type, property)) = split_member(name, "p") else {
+            continue;
+        };
+        let [(_, node), args @ .., (_, result)] = func.params.as_slice() else {
+            continue;
+        };
+        if node != "*mut ada_base_entity"
+            || !result.starts_with("*mut ")
+            || !func.return_type.ends_with("c_int")
+        {
+            continue;
+        }
+
+        let mut params = String::new();
+        let mut arg_exprs = String::new();
+        for (i, (arg, ty)) in args.iter().enumerate() {
+            let base = ty
+                .strip_prefix("*mut ")
+                .or_else(|| ty.strip_prefix("*const "));
+            match base {
+                Some(base) => {
+                    params.push_str(&format!(
+                        "ada_member_param {{ name: \"{arg}\", type_name: \"{base}\" }}, "
+                    ));
+                    arg_exprs.push_str(&format!("(*args.add({i})).cast(), "));
+                }
+                None => {
+                    params.push_str(&format!(
+                        "ada_member_param {{ name: \"{arg}\", type_name: \"{ty}\" }}, "
+                    ));
+                    arg_exprs.push_str(&format!("*(*args.add({i})).cast::<{ty}>(), "));
+                }
+            }
+        }
+        let result = result.trim_start_matches("*mut ");
+
+        thunks.push_str(&format!(
+            "#[allow(unused_variables)]
+unsafe extern \"C-unwind\" fn __thunk_{name}(
+    node: *mut ada_base_entity,
+    args: *const *mut ::core::ffi::c_void,
+    value_p: *mut ::core::ffi::c_void,
+) -> ::core::ffi::c_int {{
+    unsafe {{ {name}(node, {arg_exprs}value_p.cast()) }}
+}}
+"
+        ));
+        table.push_str(&format!(
+            "    ada_node_property {{ node_type: \"{node_type}\", name: \"{property}\", \
+             params: &[{params}], result_type: \"{result}\", thunk: __thunk_{name} }},\n"
+        ));
+    }
+
+    table.push_str("];\n");
+    thunks + &table
+}
+
+/// Remove the attributes (e.g. `#[doc = "..."]`) from a piece of the bindings.
+fn strip_attributes(code: &str) -> String {
+    let mut res = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        // unformatted bindings have a space between `#` and `[`
+        if c != '#'
+            || !chars
+                .clone()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| c == '[')
+        {
+            res.push(c);
+            continue;
+        }
+
+        let (mut depth, mut in_string, mut escaped) = (0, false, false);
+        for c in chars.by_ref() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '[' if !in_string => depth += 1,
+                ']' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    res
+}
+
+/// Generate the table of node types, with the range of node kinds they cover, an enum to name
+/// them, and the list of concrete kinds.
+///
+/// Kinds are numbered so that the concrete subtypes of each node type form a contiguous range.
+/// These ranges are declared as subtypes of `Ada_Node_Kind_Type` in the Ada specification
+/// `libadalang-common.ads`: `Ada_<Type>` for abstract types, `Ada_<Type>_Range` for concrete
+/// ones.
+fn node_types_table(bindings: &str, common_spec: &str) -> Result<String, String> {
+    // concrete kinds, in the order of the enum
+    let kinds = bindings
+        .split_once("pub enum ada_node_kind_enum {")
+        .and_then(|(_, rest)| rest.split_once('}'))
+        .map(|(body, _)| {
+            strip_attributes(body)
+                .split(',')
+                .filter_map(|variant| variant.split_once('='))
+                .map(|(name, _)| name.trim().to_owned())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut types = Vec::new();
+    if let (Some(first), Some(last)) = (kinds.first(), kinds.last()) {
+        types.push(("ada_node".to_owned(), first.clone(), last.clone()));
+    }
+
+    // subtype Ada_<Name> is Ada_Node_Kind_Type range Ada_<First> .. Ada_<Last>;
+    for decl in common_spec.split("subtype ").skip(1) {
+        let Some((decl, _)) = decl.split_once(';') else {
+            continue;
+        };
+        let words = decl.split_whitespace().collect::<Vec<_>>();
+        let [name, "is", "Ada_Node_Kind_Type", "range", first, "..", last] = words.as_slice()
+        else {
+            continue;
+        };
+        let kind = |name: &str| name.strip_prefix("Ada_").map(str::to_ascii_uppercase);
+        let (Some(name), Some(first), Some(last)) = (kind(name), kind(first), kind(last)) else {
+            continue;
+        };
+        let name = name
+            .strip_suffix("_RANGE")
+            .unwrap_or(&name)
+            .to_ascii_lowercase();
+        if kinds.contains(&first)
+            && kinds.contains(&last)
+            && !types.iter().any(|(n, _, _)| *n == name)
+        {
+            types.push((name, first, last));
+        }
+    }
+
+    // only `ada_node` means that the format of the spec has changed
+    if !types.iter().skip(1).any(|(_, first, last)| first != last) {
+        return Err("no abstract node types found in libadalang-common.ads".to_owned());
+    }
+
+    // concrete kinds without a subtype declaration
+    for kind in &kinds {
+        let name = kind.to_ascii_lowercase();
+        if !types.iter().any(|(n, _, _)| *n == name) {
+            types.push((name, kind.clone(), kind.clone()));
+        }
+    }
+
+    let mut enum_decl = String::from(
+        "#[repr(u32)]\n#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]\npub enum ada_node_type_enum {\n",
+    );
+    let mut table = String::from("pub static ada_node_types: &[ada_node_type] = &[\n");
+    for (idx, (node_type, first, last)) in types.iter().enumerate() {
+        let variant = node_type.to_ascii_uppercase();
+        let is_abstract = !kinds.contains(&variant);
+        let name = node_type
+            .split('_')
+            .map(|word| word[..1].to_ascii_uppercase() + &word[1..])
+            .collect::<String>();
+
+        enum_decl.push_str(&format!("    {variant} = {idx},\n"));
+        table.push_str(&format!(
+            "    ada_node_type {{ id: ada_node_type_enum::{variant}, node_type: \"{node_type}\", \
+             name: \"{name}\", is_abstract: {is_abstract}, \
+             first: ada_node_kind_enum::{first}, last: ada_node_kind_enum::{last} }},\n"
+        ));
+    }
+    enum_decl.push_str("}\n");
+
+    let mut kinds_table = String::from("pub static ada_node_kinds: &[ada_node_kind_enum] = &[\n");
+    let mut conversion = String::from(
+        "impl From<ada_node_kind_enum> for ada_node_type_enum {\n    \
+         fn from(kind: ada_node_kind_enum) -> Self {\n        match kind {\n",
+    );
+    for kind in &kinds {
+        kinds_table.push_str(&format!("    ada_node_kind_enum::{kind},\n"));
+        conversion.push_str(&format!(
+            "            ada_node_kind_enum::{kind} => ada_node_type_enum::{kind},\n"
+        ));
+    }
+    kinds_table.push_str("];\n");
+    conversion.push_str("        }\n    }\n}\n");
+
+    table.push_str("];\n");
+    Ok(enum_decl + &table + &kinds_table + &conversion)
+}
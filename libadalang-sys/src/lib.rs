@@ -14,3 +14,128 @@ impl Default for ada_grammar_rule {
         ada_grammar_rule::COMPILATION
     }
 }
+
+/// Accessor for a syntax field of a node, as generated for each `ada_<node>_f_<field>` function.
+pub type ada_node_field_accessor = unsafe extern "C-unwind" fn(
+    node: *mut ada_base_entity,
+    value_p: *mut ada_base_entity,
+) -> core::ffi::c_int;
+
+/// A syntax field of a node type, as found in the generated bindings.
+///
+/// Calling the accessor on a node that does not have this field returns 0.
+#[derive(Debug, Clone, Copy)]
+pub struct ada_node_field {
+    /// Lower case name of the node type declaring this field, e.g. `call_expr`.
+    pub node_type: &'static str,
+    /// Name of the field, e.g. `f_name`.
+    pub name: &'static str,
+    pub accessor: ada_node_field_accessor,
+}
+
+include!(concat!(env!("OUT_DIR"), "/node_fields.rs"));
+
+/// Uniform wrapper around a property function `ada_<node>_p_<property>`.
+///
+/// `args` points to one pointer per property argument: pointer arguments are passed as is, and
+/// other arguments are read from the pointed value. `value_p` must point to a value of the
+/// property result type.
+pub type ada_node_property_thunk = unsafe extern "C-unwind" fn(
+    node: *mut ada_base_entity,
+    args: *const *mut core::ffi::c_void,
+    value_p: *mut core::ffi::c_void,
+) -> core::ffi::c_int;
+
+/// A parameter of a property, as found in the generated bindings.
+#[derive(Debug, Clone, Copy)]
+pub struct ada_member_param {
+    pub name: &'static str,
+    /// Name of the parameter type, e.g. `ada_bool`. For pointer parameters, this is the name of
+    /// the pointed type.
+    pub type_name: &'static str,
+}
+
+/// A property of a node type, as found in the generated bindings.
+#[derive(Debug, Clone, Copy)]
+pub struct ada_node_property {
+    /// Lower case name of the node type declaring this property, e.g. `basic_decl`.
+    pub node_type: &'static str,
+    /// Name of the property, e.g. `p_is_static_decl`.
+    pub name: &'static str,
+    pub params: &'static [ada_member_param],
+    /// Name of the type pointed by the result pointer, e.g. `ada_bool`.
+    pub result_type: &'static str,
+    pub thunk: ada_node_property_thunk,
+}
+
+include!(concat!(env!("OUT_DIR"), "/node_properties.rs"));
+
+/// A node type, abstract or concrete, with the range of concrete kinds deriving from it.
+#[derive(Debug, Clone, Copy)]
+pub struct ada_node_type {
+    pub id: ada_node_type_enum,
+    /// Lower case name of the node type, e.g. `basic_decl`.
+    pub node_type: &'static str,
+    /// Name of the node type, e.g. `BasicDecl`.
+    pub name: &'static str,
+    pub is_abstract: bool,
+    pub first: ada_node_kind_enum,
+    pub last: ada_node_kind_enum,
+}
+
+include!(concat!(env!("OUT_DIR"), "/node_types.rs"));
+
+impl ada_node_type_enum {
+    fn info(self) -> &'static ada_node_type {
+        &ada_node_types[self as usize]
+    }
+
+    /// Iterate over all node types, abstract or concrete.
+    pub fn all() -> impl Iterator<Item = Self> {
+        ada_node_types.iter().map(|ty| ty.id)
+    }
+
+    /// Return the node type named `name`, e.g. `BasicDecl`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        ada_node_types
+            .iter()
+            .find(|ty| ty.name == name)
+            .map(|ty| ty.id)
+    }
+
+    /// Name of the node type, e.g. `BasicDecl`.
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    /// Whether no node can have exactly this type.
+    pub fn is_abstract(self) -> bool {
+        self.info().is_abstract
+    }
+
+    /// Iterate over the concrete kinds deriving from this node type.
+    pub fn kinds(self) -> impl Iterator<Item = ada_node_kind_enum> {
+        ada_node_kinds
+            .iter()
+            .copied()
+            .filter(move |kind| kind.is_a(self))
+    }
+}
+
+impl ada_node_kind_enum {
+    /// Iterate over all concrete node kinds.
+    pub fn all() -> impl Iterator<Item = Self> {
+        ada_node_kinds.iter().copied()
+    }
+
+    /// Name of the kind, e.g. `CallExpr`.
+    pub fn name(self) -> &'static str {
+        ada_node_type_enum::from(self).name()
+    }
+
+    /// Whether this kind derives from the node type `ty`, e.g. whether it is a `BasicDecl`.
+    pub fn is_a(self, ty: ada_node_type_enum) -> bool {
+        let ty = ty.info();
+        (ty.first as u32..=ty.last as u32).contains(&(self as u32))
+    }
+}
@@ -1,7 +1,13 @@
+pub mod compilation_unit;
 pub mod context;
+pub mod node;
+pub mod pool;
 pub mod unit;
 
+pub use compilation_unit::{CompilationUnit, UnitKind};
 pub use context::Context;
+pub use node::Node;
+pub use pool::ContextPool;
 pub use unit::Unit;
 
 use libadalang_sys::ada_grammar_rule;
@@ -0,0 +1,231 @@
+//! Structure of compilation units: kind, names and context clauses
+//!
+//! Everything here is computed from the syntax tree, without name resolution, so it works even if
+//! the dependencies of a unit cannot be found. Unit names are in lower case, like the symbols of
+//! libadalang, e.g. `ada.text_io`.
+
+use crate::{Result, introspection::Value};
+
+use super::{
+    Node, Unit,
+    node::{BaseKind, NodeKind},
+};
+
+/// Whether a compilation unit is a specification or a body.
+///
+/// Subunits, and subprogram bodies without a specification, are bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnitKind {
+    Spec,
+    Body,
+}
+
+/// A unit named in a `with` clause.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithedUnit {
+    pub name: String,
+    /// Whether this is a `limited with`
+    pub is_limited: bool,
+    /// Whether this is a `private with`
+    pub is_private: bool,
+}
+
+/// A compilation unit, i.e. a library item or a subunit, along with its context clauses.
+///
+/// Like its node, a compilation unit becomes stale when its unit is reparsed: its methods then
+/// behave as if the unit was empty, except [`CompilationUnit::name`] which returns an error.
+#[derive(Clone, Copy)]
+pub struct CompilationUnit(Node);
+
+impl CompilationUnit {
+    /// Wrap a `CompilationUnit` node. Returns `None` for other kinds of nodes.
+    pub fn from_node(node: Node) -> Option<Self> {
+        (node.kind() == NodeKind::COMPILATION_UNIT).then_some(Self(node))
+    }
+
+    /// Return the `CompilationUnit` node.
+    pub fn node(&self) -> Node {
+        self.0
+    }
+
+    /// Return the `LibraryItem` or `Subunit` node.
+    fn body(&self) -> Option<Node> {
+        field(&self.0, "f_body")
+    }
+
+    /// Whether this is a subunit, i.e. a `separate` body.
+    pub fn is_subunit(&self) -> bool {
+        self.body()
+            .is_some_and(|body| body.kind() == NodeKind::SUBUNIT)
+    }
+
+    /// Return the declaration of this unit: the library item, or the proper body of a subunit.
+    pub fn decl(&self) -> Option<Node> {
+        let body = self.body()?;
+        let field = if body.kind() == NodeKind::SUBUNIT {
+            "f_body"
+        } else {
+            "f_item"
+        };
+        self::field(&body, field)
+    }
+
+    /// Return whether this unit is a specification or a body.
+    pub fn kind(&self) -> UnitKind {
+        if self.is_subunit()
+            || self
+                .decl()
+                .is_some_and(|decl| decl.is_a(BaseKind::BODY_NODE))
+        {
+            UnitKind::Body
+        } else {
+            UnitKind::Spec
+        }
+    }
+
+    /// Whether this is a private library unit, e.g. `private package P.Q`.
+    pub fn is_private(&self) -> bool {
+        self.body()
+            .and_then(|body| field(&body, "f_has_private"))
+            .is_some_and(|node| node.kind() == NodeKind::PRIVATE_PRESENT)
+    }
+
+    /// Return the fully qualified name of this unit, e.g. `ada.text_io`. The name of a subunit is
+    /// prefixed by the name of its parent.
+    pub fn name(&self) -> Result<String> {
+        match self.0.eval("p_syntactic_fully_qualified_name", &[])? {
+            Value::Symbols(symbols) => Ok(symbols
+                .iter()
+                .map(|symbol| symbol.as_str())
+                .collect::<Vec<_>>()
+                .join(".")),
+            _ => Err(crate::Error::custom("unexpected unit name")),
+        }
+    }
+
+    /// Return the name of the parent unit of a subunit, e.g. `p` for `separate (P)`.
+    pub fn parent_name(&self) -> Option<String> {
+        let body = self.body()?;
+        if body.kind() != NodeKind::SUBUNIT {
+            return None;
+        }
+        Some(name_text(&field(&body, "f_name")?))
+    }
+
+    /// Iterate over the context clauses of this unit, i.e. its `with` and `use` clauses and its
+    /// pragmas.
+    pub fn prelude(&self) -> impl Iterator<Item = Node> + use<> {
+        field(&self.0, "f_prelude")
+            .and_then(|prelude| prelude.children().ok())
+            .into_iter()
+            .flatten()
+    }
+
+    /// Return the units named in the `with` clauses of this unit.
+    pub fn withed_units(&self) -> Vec<WithedUnit> {
+        let mut res = Vec::new();
+        for clause in self
+            .prelude()
+            .filter(|clause| clause.kind() == NodeKind::WITH_CLAUSE)
+        {
+            let is_present = |name: &str, present: NodeKind| {
+                field(&clause, name).is_some_and(|node| node.kind() == present)
+            };
+            let is_limited = is_present("f_has_limited", NodeKind::LIMITED_PRESENT);
+            let is_private = is_present("f_has_private", NodeKind::PRIVATE_PRESENT);
+            res.extend(package_names(&clause).map(|name| WithedUnit {
+                name,
+                is_limited,
+                is_private,
+            }));
+        }
+        res
+    }
+
+    /// Return the packages named in the `use` clauses of this unit (`use type` clauses are
+    /// ignored).
+    pub fn used_units(&self) -> Vec<String> {
+        self.prelude()
+            .filter(|clause| clause.kind() == NodeKind::USE_PACKAGE_CLAUSE)
+            .flat_map(|clause| package_names(&clause).collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+/// Return the non-null value of the syntax field `name` of `node`, or `None` if the node is
+/// stale.
+fn field(node: &Node, name: &str) -> Option<Node> {
+    node.field(name).ok().flatten().flatten()
+}
+
+/// Names in the `f_packages` list of a `with` or `use` clause.
+fn package_names(clause: &Node) -> impl Iterator<Item = String> + use<> {
+    field(clause, "f_packages")
+        .and_then(|packages| packages.children().ok())
+        .into_iter()
+        .flatten()
+        .map(|name| name_text(&name))
+}
+
+/// Return the lower case text of a (dotted) name, without whitespace nor comments, or an empty
+/// string if the node is stale.
+pub(crate) fn name_text(name: &Node) -> String {
+    name.traverse()
+        .into_iter()
+        .flatten()
+        .filter(|node| node.kind() == NodeKind::IDENTIFIER)
+        .filter_map(|id| id.text().ok())
+        .map(|text| text.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl Unit {
+    /// Return the compilation units of this unit. Files usually hold a single one, but there may
+    /// be several in multi-unit files.
+    pub fn compilation_units(&self) -> Vec<CompilationUnit> {
+        let Some(root) = self.root() else {
+            return Vec::new();
+        };
+        if root.kind() == NodeKind::COMPILATION_UNIT_LIST {
+            root.children()
+                .into_iter()
+                .flatten()
+                .filter_map(CompilationUnit::from_node)
+                .collect()
+        } else {
+            CompilationUnit::from_node(root).into_iter().collect()
+        }
+    }
+
+    /// Return the kind of the first compilation unit of this unit, or `None` if it has none.
+    pub fn kind(&self) -> Option<UnitKind> {
+        self.compilation_units().first().map(CompilationUnit::kind)
+    }
+
+    /// Return the fully qualified names of the compilation units of this unit.
+    pub fn unit_names(&self) -> Result<Vec<String>> {
+        self.compilation_units()
+            .iter()
+            .map(CompilationUnit::name)
+            .collect()
+    }
+
+    /// Return the units named in the `with` clauses of all the compilation units of this unit.
+    pub fn withed_units(&self) -> Vec<WithedUnit> {
+        self.compilation_units()
+            .iter()
+            .flat_map(CompilationUnit::withed_units)
+            .collect()
+    }
+
+    /// Return the packages named in the `use` clauses of all the compilation units of this unit.
+    pub fn used_units(&self) -> Vec<String> {
+        self.compilation_units()
+            .iter()
+            .flat_map(CompilationUnit::used_units)
+            .collect()
+    }
+}
@@ -1,20 +1,130 @@
 use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     ffi::{CString, c_int},
+    mem::MaybeUninit,
     num::NonZeroU8,
+    panic,
+    rc::{Rc, Weak},
 };
 
 use libadalang_sys::{
     ada_allocate_analysis_context, ada_analysis_context, ada_context_decref, ada_context_incref,
-    ada_event_handler, ada_get_analysis_unit_from_file, ada_gpr_project_initialize_context,
+    ada_context_symbol, ada_get_analysis_unit_from_buffer, ada_get_analysis_unit_from_file,
+    ada_gpr_project_initialize_context, ada_symbol_type,
 };
 
-use crate::{exception::Exception, gpr_project::GprProject};
+use crate::{
+    Result,
+    event_handler::EventHandler,
+    exception::{self, Exception},
+    gpr_project::GprProject,
+    symbol::Symbol,
+    text::Text,
+};
 
-use super::{GrammarRule, Unit};
+use super::{GrammarRule, Unit, unit};
 
 /// Reference-counted analysis context.
-#[repr(transparent)]
-pub struct Context(ada_analysis_context);
+///
+/// Contexts are neither `Send` nor `Sync`, as their reference count is not atomic and libadalang
+/// does not support concurrent accesses to a context.
+pub struct Context {
+    raw: ada_analysis_context,
+    data: Rc<ContextData>,
+}
+
+/// Options and state of a context that libadalang does not keep, shared by its handles.
+pub(crate) struct ContextData {
+    tab_stop: NonZeroU8,
+    /// Panic caught in an event handler callback, see [`resume_callback_panic`].
+    callback_panic: RefCell<Option<Box<dyn Any + Send>>>,
+}
+
+impl ContextData {
+    fn new(tab_stop: NonZeroU8) -> Self {
+        Self {
+            tab_stop,
+            callback_panic: RefCell::default(),
+        }
+    }
+}
+
+impl Drop for ContextData {
+    /// Move a panic that was not resumed to the thread, as the context has no handle left.
+    fn drop(&mut self) {
+        if let Some(payload) = self.take_callback_panic() {
+            exception::store_callback_panic(payload);
+        }
+    }
+}
+
+thread_local! {
+    /// Data of the contexts with a live handle on this thread. Contexts are not `Send`, so all
+    /// their handles are on the thread that created them.
+    ///
+    /// A live entry keeps its context alive, so its address cannot be reused by another context.
+    static CONTEXTS: RefCell<HashMap<ada_analysis_context, Weak<ContextData>>> =
+        RefCell::new(HashMap::new());
+
+    /// Number of contexts of this thread with a panic to resume, to skip looking them up when
+    /// there is none.
+    static PENDING_PANICS: Cell<usize> = const { Cell::new(0) };
+}
+
+impl ContextData {
+    pub(crate) fn tab_stop(&self) -> NonZeroU8 {
+        self.tab_stop
+    }
+
+    /// Store the panic of an event handler callback, keeping the first one.
+    pub(crate) fn store_callback_panic(&self, payload: Box<dyn Any + Send>) {
+        let mut pending = self.callback_panic.borrow_mut();
+        if pending.is_none() {
+            *pending = Some(payload);
+            PENDING_PANICS.set(PENDING_PANICS.get() + 1);
+        }
+    }
+
+    fn take_callback_panic(&self) -> Option<Box<dyn Any + Send>> {
+        let payload = self.callback_panic.take();
+        if payload.is_some() {
+            PENDING_PANICS.set(PENDING_PANICS.get() - 1);
+        }
+        payload
+    }
+}
+
+/// Resume the panic caught in an event handler callback of the context `raw`, or in another
+/// callback of this thread, if any.
+///
+/// This is called by the methods of contexts, units and nodes, so that the panic surfaces at the
+/// next use of the context after the callback.
+pub(crate) fn resume_callback_panic(raw: ada_analysis_context) {
+    if PENDING_PANICS.get() > 0
+        && let Some(payload) = data_of(raw).and_then(|data| data.take_callback_panic())
+    {
+        panic::resume_unwind(payload);
+    }
+    exception::resume_callback_panic();
+}
+
+/// Return the data of the context `raw`, if it has a live handle.
+pub(crate) fn data_of(raw: ada_analysis_context) -> Option<Rc<ContextData>> {
+    CONTEXTS.with_borrow(|contexts| contexts.get(&raw).and_then(Weak::upgrade))
+}
+
+/// Attach `data` to the context `raw`, replacing the data of a destroyed context at the same
+/// address.
+fn register(raw: ada_analysis_context, data: ContextData) -> Rc<ContextData> {
+    let data = Rc::new(data);
+    CONTEXTS.with_borrow_mut(|contexts| {
+        contexts.retain(|_, data| data.strong_count() > 0);
+        contexts.insert(raw, Rc::downgrade(&data));
+    });
+    data
+}
 
 impl Context {
     /// Build a new analysis context
@@ -27,22 +137,42 @@ impl Context {
     /// # Safety
     /// The `raw` value must be a valid analysis context, or a null pointer.
     pub unsafe fn from_raw(raw: ada_analysis_context) -> Option<Self> {
-        if raw.is_null() { None } else { Some(Self(raw)) }
+        if raw.is_null() {
+            return None;
+        }
+        let data =
+            data_of(raw).unwrap_or_else(|| register(raw, ContextData::new(DEFAULT_TAB_STOP)));
+        Some(Self { raw, data })
+    }
+
+    /// Get the inner raw analysis context.
+    pub fn as_raw(&self) -> ada_analysis_context {
+        self.raw
     }
 
+    /// Return the number of columns a tab represents in this context, see
+    /// [`ContextBuilder::tab_stop`].
+    ///
+    /// This is the default tab stop for contexts that were not built by this crate.
+    pub fn tab_stop(&self) -> NonZeroU8 {
+        self.data.tab_stop()
+    }
+
+    /// Get the unit for `filename`, parsing it if needed. With `reparse`, the file is read and
+    /// parsed again, and the nodes of the unit become stale.
     pub fn get_unit_from_file(
         &self,
         filename: &str,
         charset: &str,
         reparse: bool,
         rule: GrammarRule,
-    ) -> Result<Unit, Exception> {
+    ) -> Result<Unit> {
         let filename = CString::new(filename).unwrap();
         let charset = CString::new(charset).unwrap();
 
         let unit = unsafe {
             ada_get_analysis_unit_from_file(
-                self.0,
+                self.raw,
                 filename.as_ptr(),
                 charset.as_ptr(),
                 reparse as c_int,
@@ -50,16 +180,77 @@ impl Context {
             )
         };
 
-        Exception::wrap(unsafe { Unit::from_raw(unit) })
+        resume_callback_panic(self.raw);
+        let unit = Exception::wrap(unsafe { Unit::from_raw(unit) })?;
+        if reparse {
+            unit::bump_version(unit.as_raw());
+        }
+        Ok(unit)
+    }
+
+    /// Get the unit for `filename`, parsing it from `buffer` instead of reading the file. If the
+    /// unit already exists, it is reparsed and its nodes become stale.
+    pub fn get_unit_from_buffer(
+        &self,
+        filename: &str,
+        charset: &str,
+        buffer: &str,
+        rule: GrammarRule,
+    ) -> Result<Unit> {
+        let filename = CString::new(filename).unwrap();
+        let charset = CString::new(charset).unwrap();
+
+        let unit = unsafe {
+            ada_get_analysis_unit_from_buffer(
+                self.raw,
+                filename.as_ptr(),
+                charset.as_ptr(),
+                buffer.as_ptr().cast(),
+                buffer.len(),
+                rule,
+            )
+        };
+
+        resume_callback_panic(self.raw);
+        let unit = Exception::wrap(unsafe { Unit::from_raw(unit) })?;
+        unit::bump_version(unit.as_raw());
+        Ok(unit)
+    }
+
+    /// Intern `name` as a symbol in this context.
+    ///
+    /// The symbol is canonicalized according to Ada rules, so `context.symbol("Put_Line")` and
+    /// `context.symbol("PUT_LINE")` are equal.
+    pub fn symbol(&self, name: &str) -> Result<Symbol> {
+        self.symbol_from_text(&Text::new(name))
+    }
+
+    /// Intern the content of a `Text` as a symbol in this context.
+    pub fn symbol_from_text(&self, text: &Text) -> Result<Symbol> {
+        let mut raw_text = text.as_raw_borrow();
+        let mut symbol = MaybeUninit::<ada_symbol_type>::uninit();
+
+        resume_callback_panic(self.raw);
+        let found = unsafe { ada_context_symbol(self.raw, &raw mut raw_text, symbol.as_mut_ptr()) };
+        Exception::wrap(())?;
+
+        if found == 0 {
+            Err(crate::Error::custom(format!("invalid symbol: {text}")))
+        } else {
+            Ok(unsafe { Symbol::from_raw(symbol.assume_init()) })
+        }
     }
 }
 
+/// Number of columns a tab represents by default.
+pub(crate) const DEFAULT_TAB_STOP: NonZeroU8 = NonZeroU8::new(3).unwrap();
+
 pub struct ContextBuilder {
     gpr_project: GprProject,
     subproject: Option<CString>,
-    event_handler: Option<ada_event_handler>,
+    event_handler: Option<EventHandler>,
     with_trivia: bool,
-    tab_stop: u8,
+    tab_stop: NonZeroU8,
 }
 
 impl ContextBuilder {
@@ -70,7 +261,7 @@ impl ContextBuilder {
             subproject: None,
             event_handler: None,
             with_trivia: false,
-            tab_stop: 3,
+            tab_stop: DEFAULT_TAB_STOP,
         }
     }
 
@@ -80,10 +271,10 @@ impl ContextBuilder {
         self
     }
 
-    /// TODO
-    pub fn event_handler(mut self, event_handler: ()) -> Self {
-        #![allow(unused)]
-        todo!("Event handler")
+    /// Set the handler of the events of the built context, e.g. to provide missing units.
+    pub fn event_handler(mut self, event_handler: EventHandler) -> Self {
+        self.event_handler = Some(event_handler);
+        self
     }
 
     pub fn with_trivia(mut self, with_trivia: bool) -> Self {
@@ -93,39 +284,60 @@ impl ContextBuilder {
 
     /// Set the number of columns a tab represents
     pub fn tab_stop(mut self, tab_stop: NonZeroU8) -> Self {
-        self.tab_stop = tab_stop.get();
+        self.tab_stop = tab_stop;
         self
     }
 
     /// Consume the builder and build the context
-    pub fn finish(self) -> Result<Context, Exception> {
+    pub fn finish(self) -> Result<Context> {
+        self.create()
+    }
+
+    /// Whether the built contexts share an event handler.
+    pub(crate) fn has_event_handler(&self) -> bool {
+        self.event_handler.is_some()
+    }
+
+    /// Build a context, keeping the builder to create more contexts with the same options.
+    pub(crate) fn create(&self) -> Result<Context> {
         let ctx = unsafe { ada_allocate_analysis_context() };
-        let ctx = Context(Exception::wrap(ctx)?);
+        let raw = Exception::wrap(ctx)?;
+        // a context previously at this address was destroyed
+        unit::forget_versions(raw);
+        let ctx = Context {
+            raw,
+            data: register(raw, ContextData::new(self.tab_stop)),
+        };
 
         unsafe {
             ada_gpr_project_initialize_context(
                 self.gpr_project.as_raw(),
-                ctx.0,
+                ctx.raw,
                 crate::ptr_or_null(&self.subproject),
-                self.event_handler.unwrap_or(core::ptr::null_mut()),
+                self.event_handler
+                    .as_ref()
+                    .map_or(core::ptr::null_mut(), EventHandler::as_raw),
                 self.with_trivia as c_int,
-                self.tab_stop as c_int,
+                c_int::from(self.tab_stop.get()),
             );
         }
 
-        Exception::wrap(ctx)
+        Ok(Exception::wrap(ctx)?)
     }
 }
 
 impl Clone for Context {
     fn clone(&self) -> Self {
-        Self(unsafe { ada_context_incref(self.0) })
+        Self {
+            raw: unsafe { ada_context_incref(self.raw) },
+            data: self.data.clone(),
+        }
     }
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
-        unsafe { ada_context_decref(self.0) };
+        unsafe { ada_context_decref(self.raw) };
         Exception::log_and_ignore();
     }
 }
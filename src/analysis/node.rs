@@ -0,0 +1,244 @@
+//! Syntax tree nodes
+
+use std::{ffi::c_int, mem::MaybeUninit};
+
+use libadalang_sys::{
+    ada_analysis_unit, ada_base_entity, ada_base_node, ada_kind_name, ada_node_child,
+    ada_node_children_count, ada_node_fields, ada_node_image, ada_node_is_null,
+    ada_node_is_token_node, ada_node_kind, ada_node_kind_enum, ada_node_parent,
+    ada_node_sloc_range, ada_node_text, ada_node_type_enum, ada_node_unit,
+    ada_source_location_range,
+};
+
+use crate::{
+    Result,
+    diagnostic::SourceRange,
+    exception::{Exception, ExceptionKind},
+    text,
+};
+
+use super::{Unit, unit};
+
+/// Kind of a concrete node.
+pub type NodeKind = ada_node_kind_enum;
+
+/// A node type, abstract (e.g. `BASIC_DECL`) or concrete, to check kinds against with
+/// [`NodeKind::is_a`].
+pub type BaseKind = ada_node_type_enum;
+
+/// A node of the syntax tree, with its entity information.
+///
+/// Null nodes are represented with `Option<Node>` on the Rust side.
+///
+/// A node becomes stale when its unit is reparsed, as its memory is released by libadalang. Nodes
+/// remember the version of their unit (see [`Unit::version`]) to detect this: methods reading the
+/// node return [`Error::StaleReference`](crate::Error::StaleReference) instead of reading freed
+/// memory. The kind and the unit of a node are kept in the handle, so they remain available.
+#[derive(Clone, Copy)]
+pub struct Node {
+    raw: ada_base_entity,
+    unit: ada_analysis_unit,
+    kind: NodeKind,
+    version: u64,
+}
+
+impl Node {
+    /// Create a node from a raw entity. Returns `None` for a null node.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid entity, whose unit has not been reparsed or destroyed.
+    pub unsafe fn from_raw(mut raw: ada_base_entity) -> Option<Self> {
+        if unsafe { ada_node_is_null(&raw mut raw) } != 0 {
+            None
+        } else {
+            Some(Self::new(raw))
+        }
+    }
+
+    /// Create a node from a bare node, without entity information. Returns `None` for a null
+    /// node.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid node, or a null pointer.
+    pub unsafe fn from_bare(raw: ada_base_node) -> Option<Self> {
+        if raw.is_null() {
+            return None;
+        }
+        // SAFETY: null entity information is valid, and means no information
+        let mut entity = unsafe { core::mem::zeroed::<ada_base_entity>() };
+        entity.node = raw;
+        Some(Self::new(entity))
+    }
+
+    fn new(mut raw: ada_base_entity) -> Self {
+        Self {
+            raw,
+            unit: unsafe { ada_node_unit(&raw mut raw) },
+            kind: unsafe { ada_node_kind(&raw mut raw) },
+            version: unit::current_version(),
+        }
+    }
+
+    /// Get a copy of the inner raw entity.
+    pub fn as_raw(&self) -> ada_base_entity {
+        self.raw
+    }
+
+    /// Mutable pointer to a copy of the entity, as expected by most C functions, or an error if
+    /// the node is stale.
+    ///
+    /// The C API never mutates the entities it is passed.
+    pub(crate) fn as_ptr(&self) -> Result<*mut ada_base_entity> {
+        self.check()?;
+        Ok((&raw const self.raw).cast_mut())
+    }
+
+    /// Whether the unit of this node was reparsed since the node was created, which makes it
+    /// unusable.
+    pub fn is_stale(&self) -> bool {
+        self.version != unit::current_version() && unit::version_of(self.unit) > self.version
+    }
+
+    /// Return an error if this node is stale.
+    ///
+    /// # Panics
+    /// Resumes the panic of an event handler callback of the context of this node, like the
+    /// other methods reading the node.
+    pub fn check(&self) -> Result<()> {
+        unit::resume_callback_panic(self.unit);
+        if self.is_stale() {
+            Err(Exception::new(
+                ExceptionKind::STALE_REFERENCE_ERROR,
+                "use of a stale node, whose unit was reparsed",
+            )
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Return the kind of this node.
+    pub fn kind(&self) -> NodeKind {
+        unit::resume_callback_panic(self.unit);
+        self.kind
+    }
+
+    /// Whether the kind of this node derives from the node type `ty`.
+    pub fn is_a(&self, ty: BaseKind) -> bool {
+        self.kind().is_a(ty)
+    }
+
+    /// Return the name of this node's kind, e.g. `CallExpr`.
+    pub fn kind_name(&self) -> String {
+        let mut text = text::empty_raw();
+        unsafe { ada_kind_name(self.kind(), &raw mut text) };
+        text::take_string(text)
+    }
+
+    /// Return the source text covered by this node.
+    pub fn text(&self) -> Result<String> {
+        let mut text = text::empty_raw();
+        unsafe { ada_node_text(self.as_ptr()?, &raw mut text) };
+        Ok(text::take_string(text))
+    }
+
+    /// Return a debug representation of this node.
+    pub fn image(&self) -> Result<String> {
+        let mut text = text::empty_raw();
+        unsafe { ada_node_image(self.as_ptr()?, &raw mut text) };
+        Ok(text::take_string(text))
+    }
+
+    /// Return the source location range covered by this node.
+    pub fn sloc_range(&self) -> Result<SourceRange> {
+        let mut range = MaybeUninit::<ada_source_location_range>::uninit();
+        unsafe { ada_node_sloc_range(self.as_ptr()?, range.as_mut_ptr()) };
+        Ok(SourceRange::from_raw(unsafe { range.assume_init() }))
+    }
+
+    /// Return whether this node is a token node (its text is a single token).
+    pub fn is_token_node(&self) -> Result<bool> {
+        Ok(unsafe { ada_node_is_token_node(self.as_ptr()?) != 0 })
+    }
+
+    /// Return the analysis unit that owns this node.
+    ///
+    /// Units outlive their nodes, so this also works for stale nodes.
+    pub fn unit(&self) -> Unit {
+        unsafe { Unit::from_raw(self.unit) }
+    }
+
+    /// Return the syntactic parent of this node, or `None` for the root node.
+    pub fn parent(&self) -> Result<Option<Node>> {
+        let mut parent = MaybeUninit::<ada_base_entity>::uninit();
+        let found = unsafe { ada_node_parent(self.as_ptr()?, parent.as_mut_ptr()) };
+        let parent = Exception::wrap(parent)?;
+
+        if found == 0 {
+            Ok(None)
+        } else {
+            Ok(unsafe { Node::from_raw(parent.assume_init()) })
+        }
+    }
+
+    /// Return the number of children of this node, null children included.
+    pub fn children_count(&self) -> Result<u32> {
+        Ok(unsafe { ada_node_children_count(self.as_ptr()?) })
+    }
+
+    /// Return the `n`-th child of this node, or `None` if it is out of bounds or a null node.
+    pub fn child(&self, n: u32) -> Result<Option<Node>> {
+        let mut child = MaybeUninit::<ada_base_entity>::uninit();
+        let found = unsafe { ada_node_child(self.as_ptr()?, n, child.as_mut_ptr()) };
+        if found == 0 {
+            Ok(None)
+        } else {
+            Ok(unsafe { Node::from_raw(child.assume_init()) })
+        }
+    }
+
+    /// Iterate over the non-null children of this node.
+    ///
+    /// The iteration stops if the node becomes stale.
+    pub fn children(&self) -> Result<impl Iterator<Item = Node> + use<>> {
+        let node = *self;
+        Ok((0..self.children_count()?)
+            .map_while(move |n| node.child(n).ok())
+            .flatten())
+    }
+
+    /// Iterate over this node and all its non-null descendants, in prefix order.
+    ///
+    /// The iteration stops if the unit of the nodes is reparsed.
+    pub fn traverse(&self) -> Result<impl Iterator<Item = Node> + use<>> {
+        self.check()?;
+        let mut stack = vec![*self];
+        Ok(core::iter::from_fn(move || {
+            let node = stack.pop()?;
+            let count = node.children_count().ok()?;
+            for n in (0..count).rev() {
+                stack.extend(node.child(n).ok()?);
+            }
+            Some(node)
+        }))
+    }
+
+    /// Return the value of the syntax field named `name` (e.g. `f_name`).
+    ///
+    /// Returns `None` if this node has no such field, and `Some(None)` if the field is a null node.
+    pub fn field(&self, name: &str) -> Result<Option<Option<Node>>> {
+        let ptr = self.as_ptr()?;
+        Ok(ada_node_fields
+            .iter()
+            .filter(|field| field.name == name)
+            .find_map(|field| {
+                let mut value = MaybeUninit::<ada_base_entity>::uninit();
+                let found: c_int = unsafe { (field.accessor)(ptr, value.as_mut_ptr()) };
+                if found == 0 || Exception::get_last().is_some() {
+                    None
+                } else {
+                    Some(unsafe { Node::from_raw(value.assume_init()) })
+                }
+            }))
+    }
+}
@@ -0,0 +1,178 @@
+//! Pools of analysis contexts, to analyze files on several threads
+
+use std::{
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{Error, Result};
+
+use super::{Context, GrammarRule, Unit, context::ContextBuilder};
+
+type Job = Box<dyn FnOnce(&Context) + Send>;
+
+/// A pool of worker threads, each with its own analysis context.
+///
+/// The contexts are created with the same [`ContextBuilder`], hence from the same GPR project.
+/// Jobs are run by the first idle worker, so a file may be analyzed by any of the contexts.
+pub struct ContextPool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ContextPool {
+    /// Start `threads` workers, with contexts created by `builder`.
+    ///
+    /// Fails if the builder has an event handler, as it cannot be shared between threads.
+    pub fn new(builder: ContextBuilder, threads: NonZeroUsize) -> Result<Self> {
+        if builder.has_event_handler() {
+            return Err(Error::custom(
+                "contexts of a pool cannot share an event handler",
+            ));
+        }
+
+        let builder = Arc::new(Mutex::new(builder));
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let (ready, started) = mpsc::channel();
+
+        let workers = (0..threads.get())
+            .map(|_| {
+                let builder = Arc::clone(&builder);
+                let receiver = Arc::clone(&receiver);
+                let ready = ready.clone();
+                thread::spawn(move || {
+                    // the builder is locked since libadalang does not support initializing
+                    // contexts from the same project concurrently
+                    let ctx = builder.lock().unwrap_or_else(|e| e.into_inner()).create();
+                    let ctx = match ctx {
+                        Ok(ctx) => {
+                            let _ = ready.send(Ok(()));
+                            ctx
+                        }
+                        Err(err) => {
+                            let _ = ready.send(Err(err));
+                            return;
+                        }
+                    };
+                    drop(ready);
+                    worker(&ctx, &receiver);
+                    // keep the project alive until the context is dropped
+                    drop(ctx);
+                    drop(builder);
+                })
+            })
+            .collect();
+        drop(ready);
+
+        let pool = Self {
+            jobs: Some(jobs),
+            workers,
+        };
+        for res in started {
+            res?;
+        }
+        Ok(pool)
+    }
+
+    /// Number of worker threads of the pool.
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `job` on the context of an idle worker.
+    ///
+    /// Panics in the job are caught, and do not stop the worker.
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce(&Context) + Send + 'static,
+    {
+        self.jobs
+            .as_ref()
+            .expect("the pool is running")
+            .send(Box::new(job))
+            .expect("the workers of the pool are running");
+    }
+
+    /// Parse each file of `files` in the context of a worker, and call `f` on its unit.
+    ///
+    /// `progress` is called on the calling thread each time a file is done, with its index in
+    /// `files`.
+    ///
+    /// Returns the results of `f` in the order of `files`, or the error raised when getting the
+    /// unit. If `f` panics, the panic is resumed on the calling thread once all files are done.
+    pub fn map_files<I, F, R>(
+        &self,
+        files: I,
+        f: F,
+        mut progress: impl FnMut(usize),
+    ) -> Vec<Result<R>>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+        F: Fn(Unit) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let f = Arc::new(f);
+        let (results, receiver) = mpsc::channel();
+
+        let mut count = 0;
+        for (idx, file) in files.into_iter().enumerate() {
+            let file = file.into();
+            let f = Arc::clone(&f);
+            let results = results.clone();
+            self.execute(move |ctx| {
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    ctx.get_unit_from_file(&file, "", false, GrammarRule::COMPILATION)
+                        .map(|unit| f(unit))
+                }));
+                let _ = results.send((idx, res));
+            });
+            count += 1;
+        }
+        drop(results);
+
+        let mut res = (0..count).map(|_| None).collect::<Vec<_>>();
+        let mut panic = None;
+        for (idx, r) in receiver {
+            match r {
+                Ok(r) => res[idx] = Some(r),
+                Err(payload) => panic = Some(payload),
+            }
+            progress(idx);
+        }
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+
+        res.into_iter()
+            .map(|r| r.expect("every job sends its result"))
+            .collect()
+    }
+}
+
+fn worker(ctx: &Context, jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok(job) = job else {
+            // the pool was dropped
+            return;
+        };
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| job(ctx)));
+    }
+}
+
+impl Drop for ContextPool {
+    /// Wait for the pending jobs to finish, then stop the workers.
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
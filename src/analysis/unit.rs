@@ -1,22 +1,98 @@
 //! Analysis units
 
 use std::{
-    ffi::{CStr, c_void},
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    ffi::{CStr, CString, c_void},
     mem::MaybeUninit,
+    num::NonZeroU8,
+    rc::Rc,
 };
 
 use libadalang_sys::{
-    ada_analysis_unit, ada_diagnostic, ada_unit_context, ada_unit_diagnostic,
-    ada_unit_diagnostic_count, ada_unit_filename, ada_unit_token_count, ada_unit_trivia_count,
+    ada_analysis_context, ada_analysis_unit, ada_base_entity, ada_diagnostic, ada_token,
+    ada_token_range_text, ada_unit_context, ada_unit_diagnostic, ada_unit_diagnostic_count,
+    ada_unit_filename, ada_unit_first_token, ada_unit_last_token, ada_unit_reparse_from_buffer,
+    ada_unit_reparse_from_file, ada_unit_root, ada_unit_token_count, ada_unit_trivia_count,
 };
 
-use crate::diagnostic::Diagnostic;
+use crate::{
+    Result,
+    diagnostic::{Diagnostic, DiagnosticOrigin, Severity},
+    exception::Exception,
+    file_reader, text,
+};
 
-use super::Context;
+use super::{
+    Context, Node,
+    context::{self, ContextData, DEFAULT_TAB_STOP},
+};
 
 /// A libadalang analysis unit
+///
+/// Units belong to their context, and like it are neither `Send` nor `Sync`.
 pub struct Unit(ada_analysis_unit);
 
+thread_local! {
+    /// Number of reparses done so far on this thread. Contexts are not `Send`, so the units of a
+    /// context are all reparsed on its thread.
+    static REPARSES: Cell<u64> = const { Cell::new(0) };
+
+    /// Version of the reparsed units of each context of this thread, see [`Unit::version`].
+    ///
+    /// Versions are kept as long as the context may live, and not only while it has a handle, as
+    /// [`Context::from_raw`] can give new handles to it. Units live as long as their context, so
+    /// their addresses are not reused before the context is destroyed, which is only known for
+    /// sure once a new context is allocated at its address.
+    static UNIT_VERSIONS: RefCell<HashMap<ada_analysis_context, HashMap<ada_analysis_unit, u64>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Version given to the nodes created now on this thread.
+pub(crate) fn current_version() -> u64 {
+    REPARSES.get()
+}
+
+/// Return the version of `unit`, i.e. the value of the reparse counter of its thread when it was
+/// last reparsed.
+pub(crate) fn version_of(unit: ada_analysis_unit) -> u64 {
+    context_unit_version(unsafe { ada_unit_context(unit) }, unit)
+}
+
+fn context_unit_version(context: ada_analysis_context, unit: ada_analysis_unit) -> u64 {
+    UNIT_VERSIONS.with_borrow(|versions| {
+        versions
+            .get(&context)
+            .and_then(|units| units.get(&unit))
+            .copied()
+            .unwrap_or(0)
+    })
+}
+
+/// Resume the panic of an event handler callback of the context of `unit`, if any, see
+/// [`context::resume_callback_panic`].
+pub(crate) fn resume_callback_panic(unit: ada_analysis_unit) {
+    context::resume_callback_panic(unsafe { ada_unit_context(unit) });
+}
+
+/// Record that `unit` was reparsed, which makes its nodes stale.
+pub(crate) fn bump_version(unit: ada_analysis_unit) {
+    record_reparse(unsafe { ada_unit_context(unit) }, unit);
+}
+
+fn record_reparse(context: ada_analysis_context, unit: ada_analysis_unit) {
+    let version = REPARSES.get() + 1;
+    REPARSES.set(version);
+    UNIT_VERSIONS.with_borrow_mut(|versions| {
+        versions.entry(context).or_default().insert(unit, version);
+    });
+}
+
+/// Forget the versions of the units of `context`, once it is known to be destroyed.
+pub(crate) fn forget_versions(context: ada_analysis_context) {
+    UNIT_VERSIONS.with_borrow_mut(|versions| versions.remove(&context));
+}
+
 impl Unit {
     /// Create a new analysis unit from its raw value
     ///
@@ -26,42 +102,199 @@ impl Unit {
         Self(raw)
     }
 
+    /// Get the inner raw analysis unit.
+    pub fn as_raw(&self) -> ada_analysis_unit {
+        self.0
+    }
+
+    /// Resume the panic of an event handler callback of the context of this unit, if any.
+    fn resume_callback_panic(&self) {
+        resume_callback_panic(self.0);
+    }
+
+    /// Return the version of this unit, which changes each time it is reparsed.
+    ///
+    /// Nodes created before the last reparse are stale, see [`Node::is_stale`].
+    pub fn version(&self) -> u64 {
+        version_of(self.0)
+    }
+
+    /// Reparse this unit from its file, with the given `charset` (the charset of the context if
+    /// empty).
+    ///
+    /// All the nodes of this unit become stale. Taking `&mut self` prevents reparsing while the
+    /// unit is borrowed, e.g. while iterating over its diagnostics; nodes do not borrow their unit,
+    /// and using them after the reparse is caught at runtime instead.
+    pub fn reparse_from_file(&mut self, charset: &str) -> Result<()> {
+        let charset = CString::new(charset).unwrap();
+        unsafe { ada_unit_reparse_from_file(self.0, charset.as_ptr()) };
+        bump_version(self.0);
+        self.resume_callback_panic();
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Reparse this unit from `buffer`, with the given `charset` (the charset of the context if
+    /// empty).
+    ///
+    /// All the nodes of this unit become stale, see [`Unit::reparse_from_file`].
+    pub fn reparse_from_buffer(&mut self, charset: &str, buffer: &str) -> Result<()> {
+        let charset = CString::new(charset).unwrap();
+        unsafe {
+            ada_unit_reparse_from_buffer(
+                self.0,
+                charset.as_ptr(),
+                buffer.as_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        bump_version(self.0);
+        self.resume_callback_panic();
+        Ok(Exception::wrap(())?)
+    }
+
     /// Return the context that owns this unit.
     pub fn context(&self) -> Option<Context> {
         unsafe { Context::from_raw(ada_unit_context(self.0)) }
     }
 
+    /// Return the data of the context that owns this unit, without taking a reference to the
+    /// context.
+    pub(crate) fn context_data(&self) -> Option<Rc<ContextData>> {
+        context::data_of(unsafe { ada_unit_context(self.0) })
+    }
+
+    /// Return the number of columns a tab represents in the context of this unit, see
+    /// [`Context::tab_stop`].
+    pub fn tab_stop(&self) -> NonZeroU8 {
+        self.context_data()
+            .map_or(DEFAULT_TAB_STOP, |data| data.tab_stop())
+    }
+
     /// Return the filename this unit is associated to.
     pub fn filename(&self) -> String {
+        self.resume_callback_panic();
         let ptr = unsafe { ada_unit_filename(self.0) };
         let res = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_owned();
         unsafe { libadalang_sys::ada_free(ptr as *mut c_void) };
         res
     }
 
+    /// Return the root node of this unit, or `None` if the unit has no root (e.g. it could not be
+    /// read).
+    pub fn root(&self) -> Option<Node> {
+        self.resume_callback_panic();
+        let mut root = MaybeUninit::<ada_base_entity>::uninit();
+        unsafe { ada_unit_root(self.0, root.as_mut_ptr()) };
+        unsafe { Node::from_raw(root.assume_init()) }
+    }
+
+    /// Return the source text of this unit, from its first to its last token.
+    ///
+    /// This is the text libadalang parsed. Note that it does not include trivia before the first
+    /// token if the context was created without trivia.
+    pub fn unparse(&self) -> Result<String> {
+        self.resume_callback_panic();
+        let mut first = MaybeUninit::<ada_token>::uninit();
+        let mut last = MaybeUninit::<ada_token>::uninit();
+        let mut text = text::empty_raw();
+
+        unsafe {
+            ada_unit_first_token(self.0, first.as_mut_ptr());
+            ada_unit_last_token(self.0, last.as_mut_ptr());
+            ada_token_range_text(first.as_mut_ptr(), last.as_mut_ptr(), &raw mut text);
+        }
+
+        Ok(Exception::wrap(text::take_string(text))?)
+    }
+
     pub fn token_count(&self) -> usize {
+        self.resume_callback_panic();
         let cnt = unsafe { ada_unit_token_count(self.0) };
         usize::try_from(cnt).unwrap()
     }
 
     pub fn trivia_count(&self) -> usize {
+        self.resume_callback_panic();
         let cnt = unsafe { ada_unit_trivia_count(self.0) };
         usize::try_from(cnt).unwrap()
     }
 
     pub fn diagnostic_count(&self) -> u32 {
+        self.resume_callback_panic();
         let cnt = unsafe { ada_unit_diagnostic_count(self.0) };
         u32::try_from(cnt).unwrap()
     }
 
     pub fn get_diagnostic(&self, idx: u32) -> Option<Diagnostic> {
+        self.resume_callback_panic();
         let mut diag = MaybeUninit::<ada_diagnostic>::uninit();
         let found = unsafe { ada_unit_diagnostic(self.0, idx, diag.as_mut_ptr()) };
         if found == 0 {
             None
         } else {
-            let res = Diagnostic::from_raw(unsafe { diag.assume_init() });
-            Some(res)
+            let filename = self.filename();
+            let mut res = Diagnostic::from_raw(unsafe { diag.assume_init() });
+            // parsing diagnostics always have a location, unlike those of libadalang's file
+            // readers, e.g. when the file cannot be opened
+            if res.sloc_range.start.line == 0 || file_reader::is_read_diagnostic(&filename, &res) {
+                res.origin = DiagnosticOrigin::Reader;
+            }
+            Some(res.with_filename(filename))
         }
     }
+
+    /// Iterate over the diagnostics of this unit.
+    pub fn diagnostics(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        (0..self.diagnostic_count()).filter_map(|idx| self.get_diagnostic(idx))
+    }
+
+    /// Whether this unit has error diagnostics.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics()
+            .any(|diag| diag.severity == Severity::Error)
+    }
+
+    /// Return the diagnostics of this unit, grouped by severity.
+    pub fn diagnostics_by_severity(&self) -> BTreeMap<Severity, Vec<Diagnostic>> {
+        let mut res = BTreeMap::<_, Vec<_>>::new();
+        for diag in self.diagnostics() {
+            res.entry(diag.severity).or_default().push(diag);
+        }
+        res
+    }
+
+    /// Whether the file reader, or the preprocessor, reported a diagnostic for this unit, see
+    /// [`DiagnosticOrigin::Reader`].
+    pub fn has_read_error(&self) -> bool {
+        self.diagnostics()
+            .any(|diag| diag.origin == DiagnosticOrigin::Reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_outlive_context_handles() {
+        let context: ada_analysis_context = std::ptr::without_provenance_mut(0x1000);
+        let unit: ada_analysis_unit = std::ptr::without_provenance_mut(0x2000);
+        let node_version = current_version();
+
+        // the unit is reparsed while the context has no handle on this thread
+        record_reparse(context, unit);
+        let version = context_unit_version(context, unit);
+        assert!(version > node_version);
+
+        // a handle rebuilt from the raw context registers new data, but keeps the versions
+        let handle = unsafe { Context::from_raw(context) }.unwrap();
+        assert_eq!(context_unit_version(context, unit), version);
+        record_reparse(context, unit);
+        assert!(context_unit_version(context, unit) > version);
+        // the context is not real, so it must not be released
+        std::mem::forget(handle);
+
+        forget_versions(context);
+        assert_eq!(context_unit_version(context, unit), 0);
+    }
 }
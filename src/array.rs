@@ -0,0 +1,220 @@
+//! Reference-counted arrays returned by properties
+
+use std::{ffi::c_void, ops::Deref};
+
+use libadalang_sys::{
+    ada_analysis_unit, ada_base_entity, ada_string_type, ada_symbol_type, ada_text,
+};
+
+use crate::{
+    analysis::{Node, Unit},
+    symbol::Symbol,
+    text::Text,
+};
+
+/// A raw `ada_*_array` type, i.e. a pointer to a reference-counted array record.
+///
+/// # Safety
+/// Implementors must be pointers to array records with a valid length and items.
+pub unsafe trait RawArray: Copy {
+    /// Type of the items in the array
+    type Item;
+
+    /// Convert the array into an untyped pointer.
+    fn into_ptr(self) -> *mut c_void;
+
+    /// Convert back an untyped pointer obtained with [`RawArray::into_ptr`].
+    ///
+    /// # Safety
+    /// The pointer must come from `into_ptr` on the same array type.
+    unsafe fn from_ptr(ptr: *mut c_void) -> Self;
+
+    /// Get the items of the array.
+    ///
+    /// # Safety
+    /// The array must be a valid, non-null pointer, and the returned slice must not outlive it.
+    unsafe fn items<'a>(self) -> &'a [Self::Item];
+
+    /// Decrement the reference count of the array.
+    ///
+    /// # Safety
+    /// The array must be a valid, non-null pointer.
+    unsafe fn dec_ref(self);
+}
+
+/// Conversion from a raw array item to its safe Rust counterpart.
+///
+/// The item is still owned by the array, so the conversion must copy it. Items that may be null
+/// (e.g. nodes) convert to an `Option`, so that indices match the raw array.
+pub trait FromRawItem<R>: Sized {
+    /// Convert a raw array item.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid item, from a live array.
+    unsafe fn from_raw_item(raw: &R) -> Self;
+}
+
+/// A reference-counted array returned by libadalang, with its items converted to Rust values.
+///
+/// The items are copied when the array is created, and the raw array is released at once.
+pub struct LalArray<T> {
+    items: Vec<T>,
+}
+
+impl<T> LalArray<T> {
+    /// Create an array from its raw value, taking ownership of the reference.
+    ///
+    /// Returns `None` for a null array.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid array, or a null pointer.
+    pub unsafe fn from_raw<A>(raw: A) -> Option<Self>
+    where
+        A: RawArray,
+        T: FromRawItem<A::Item>,
+    {
+        if raw.into_ptr().is_null() {
+            return None;
+        }
+
+        let items = unsafe { raw.items() }
+            .iter()
+            .map(|item| unsafe { T::from_raw_item(item) })
+            .collect();
+        unsafe { raw.dec_ref() };
+        crate::exception::Exception::log_and_ignore();
+
+        Some(Self { items })
+    }
+
+    /// Convert the array into a vector of its items.
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T> Deref for LalArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> AsRef<[T]> for LalArray<T> {
+    fn as_ref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T> IntoIterator for LalArray<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LalArray<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for LalArray<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(&self.items).finish()
+    }
+}
+
+impl FromRawItem<ada_base_entity> for Option<Node> {
+    unsafe fn from_raw_item(raw: &ada_base_entity) -> Self {
+        unsafe { Node::from_raw(*raw) }
+    }
+}
+
+impl FromRawItem<ada_analysis_unit> for Option<Unit> {
+    unsafe fn from_raw_item(raw: &ada_analysis_unit) -> Self {
+        (!raw.is_null()).then(|| unsafe { Unit::from_raw(*raw) })
+    }
+}
+
+impl FromRawItem<ada_symbol_type> for Symbol {
+    unsafe fn from_raw_item(raw: &ada_symbol_type) -> Self {
+        unsafe { Symbol::from_raw(*raw) }
+    }
+}
+
+impl FromRawItem<ada_text> for String {
+    unsafe fn from_raw_item(raw: &ada_text) -> Self {
+        String::from(Text::from_raw_borrow(raw))
+    }
+}
+
+impl FromRawItem<ada_string_type> for String {
+    unsafe fn from_raw_item(raw: &ada_string_type) -> Self {
+        unsafe { crate::text::string_from_raw(*raw) }
+    }
+}
+
+macro_rules! raw_arrays {
+    ($($array:ident => $dec_ref:ident, $item:ty;)*) => { $(
+        unsafe impl RawArray for libadalang_sys::$array {
+            type Item = $item;
+
+            fn into_ptr(self) -> *mut c_void {
+                self.cast()
+            }
+
+            unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+                ptr.cast()
+            }
+
+            unsafe fn items<'a>(self) -> &'a [Self::Item] {
+                let len = usize::try_from(unsafe { (*self).n }).unwrap_or(0);
+                let ptr = unsafe { (&raw const (*self).items) }.cast::<$item>();
+                unsafe { core::slice::from_raw_parts(ptr, len) }
+            }
+
+            unsafe fn dec_ref(self) {
+                unsafe { libadalang_sys::$dec_ref(self) }
+            }
+        }
+    )* };
+}
+
+raw_arrays! {
+    ada_ada_node_array => ada_ada_node_array_dec_ref, ada_base_entity;
+    ada_base_formal_param_decl_array => ada_base_formal_param_decl_array_dec_ref, ada_base_entity;
+    ada_base_type_decl_array => ada_base_type_decl_array_dec_ref, ada_base_entity;
+    ada_basic_decl_array => ada_basic_decl_array_dec_ref, ada_base_entity;
+    ada_compilation_unit_array => ada_compilation_unit_array_dec_ref, ada_base_entity;
+    ada_defining_name_array => ada_defining_name_array_dec_ref, ada_base_entity;
+    ada_expr_array => ada_expr_array_dec_ref, ada_base_entity;
+    ada_generic_instantiation_array => ada_generic_instantiation_array_dec_ref, ada_base_entity;
+    ada_param_spec_array => ada_param_spec_array_dec_ref, ada_base_entity;
+    ada_pragma_node_array => ada_pragma_node_array_dec_ref, ada_base_entity;
+    ada_type_decl_array => ada_type_decl_array_dec_ref, ada_base_entity;
+    ada_analysis_unit_array => ada_analysis_unit_array_dec_ref, ada_analysis_unit;
+    ada_unbounded_text_type_array => ada_unbounded_text_type_array_dec_ref, ada_symbol_type;
+    ada_internal_discriminant_values_array => ada_internal_discriminant_values_array_dec_ref,
+        libadalang_sys::ada_internal_discriminant_values;
+    ada_internal_param_actual_array => ada_internal_param_actual_array_dec_ref,
+        libadalang_sys::ada_internal_param_actual;
+    ada_internal_shape_array => ada_internal_shape_array_dec_ref,
+        libadalang_sys::ada_internal_shape;
+    ada_internal_ref_result_array => ada_internal_ref_result_array_dec_ref,
+        libadalang_sys::ada_internal_ref_result;
+    ada_internal_param_match_array => ada_internal_param_match_array_dec_ref,
+        libadalang_sys::ada_internal_param_match;
+    ada_internal_completion_item_array => ada_internal_completion_item_array_dec_ref,
+        libadalang_sys::ada_internal_completion_item;
+    ada_internal_doc_annotation_array => ada_internal_doc_annotation_array_dec_ref,
+        libadalang_sys::ada_internal_doc_annotation;
+    ada_internal_substitution_array => ada_internal_substitution_array_dec_ref,
+        libadalang_sys::ada_internal_substitution;
+}
@@ -0,0 +1,170 @@
+//! Arbitrary precision integers, as returned by static evaluation properties
+
+use std::{error::Error, fmt::Display, ptr::NonNull, str::FromStr};
+
+use libadalang_sys::{
+    ada_big_integer, ada_big_integer__struct, ada_big_integer_decref, ada_big_integer_text,
+    ada_create_big_integer,
+};
+
+use crate::{
+    Result,
+    exception::Exception,
+    text::{self, Text},
+};
+
+/// A reference-counted big integer from libadalang.
+pub struct BigInt(NonNull<ada_big_integer__struct>);
+
+/// Error returned when a big integer does not fit in the requested integer type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRangeError;
+
+impl Display for OutOfRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("big integer out of range for the target type")
+    }
+}
+
+impl Error for OutOfRangeError {}
+
+impl BigInt {
+    /// Create a big integer from its decimal representation, e.g. `-12345`.
+    pub fn new(decimal: &str) -> Result<Self> {
+        Self::from_text(&Text::new(decimal))
+    }
+
+    /// Create a big integer from a `Text` holding its decimal representation.
+    pub fn from_text(text: &Text) -> Result<Self> {
+        let mut raw = text.as_raw_borrow();
+        let res = unsafe { ada_create_big_integer(&raw mut raw) };
+        let res = Exception::wrap(res)?;
+        Ok(unsafe { Self::from_raw(res) }.expect("libadalang returned a null big integer"))
+    }
+
+    /// Create a big integer from its raw value, taking ownership of the reference. Returns `None`
+    /// for a null pointer.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid big integer, or a null pointer.
+    pub unsafe fn from_raw(raw: ada_big_integer) -> Option<Self> {
+        NonNull::new(raw).map(Self)
+    }
+
+    /// Unwrap the big integer into its raw value, without decrementing its reference count.
+    pub fn into_raw(self) -> ada_big_integer {
+        let no_drop = core::mem::ManuallyDrop::new(self);
+        no_drop.0.as_ptr()
+    }
+
+    /// Get the inner raw pointer to the big integer.
+    pub fn as_raw(&self) -> ada_big_integer {
+        self.0.as_ptr()
+    }
+
+    /// Return the decimal representation of this big integer.
+    pub fn to_decimal(&self) -> String {
+        let mut text = text::empty_raw();
+        unsafe { ada_big_integer_text(self.as_raw(), &raw mut text) };
+        text::take_string(text)
+    }
+}
+
+impl Clone for BigInt {
+    /// The C API has no way to share a big integer, so this creates a new one with the same value.
+    fn clone(&self) -> Self {
+        Self::new(&self.to_decimal()).unwrap()
+    }
+}
+
+impl PartialEq for BigInt {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_decimal() == other.to_decimal()
+    }
+}
+
+impl Eq for BigInt {}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_decimal())
+    }
+}
+
+impl std::fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BigInt({})", self.to_decimal())
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+macro_rules! impl_int_conversions {
+    ($($int:ty),*) => { $(
+        impl TryFrom<&BigInt> for $int {
+            type Error = OutOfRangeError;
+
+            fn try_from(value: &BigInt) -> Result<Self, Self::Error> {
+                value.to_decimal().parse().map_err(|_| OutOfRangeError)
+            }
+        }
+
+        impl TryFrom<BigInt> for $int {
+            type Error = OutOfRangeError;
+
+            fn try_from(value: BigInt) -> Result<Self, Self::Error> {
+                Self::try_from(&value)
+            }
+        }
+
+        impl From<$int> for BigInt {
+            fn from(value: $int) -> Self {
+                Self::new(&value.to_string()).unwrap()
+            }
+        }
+    )* };
+}
+
+impl_int_conversions!(i64, u64, i128, u128);
+
+#[cfg(feature = "num-bigint")]
+impl From<&BigInt> for num_bigint::BigInt {
+    fn from(value: &BigInt) -> Self {
+        // libadalang always produces a valid decimal representation
+        value.to_decimal().parse().unwrap()
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<BigInt> for num_bigint::BigInt {
+    fn from(value: BigInt) -> Self {
+        Self::from(&value)
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<&num_bigint::BigInt> for BigInt {
+    fn from(value: &num_bigint::BigInt) -> Self {
+        Self::new(&value.to_str_radix(10)).unwrap()
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<num_bigint::BigInt> for BigInt {
+    fn from(value: num_bigint::BigInt) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl Drop for BigInt {
+    fn drop(&mut self) {
+        unsafe { ada_big_integer_decref(self.0.as_ptr()) };
+        Exception::log_and_ignore();
+    }
+}
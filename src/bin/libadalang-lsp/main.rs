@@ -0,0 +1,105 @@
+//! A Language Server Protocol server for Ada, over stdio
+//!
+//! The server loads a GPR project (`-P`, or an implicit project in the current directory) and
+//! provides diagnostics, go to definition, find references, hover and document symbols. The
+//! documents are fully synchronized, and parsed with the project's context.
+//!
+//! Find references uses a cross-reference index of the whole project, built on the first request
+//! and then kept up to date with the open documents.
+
+mod protocol;
+mod server;
+mod transport;
+
+use std::{num::NonZeroUsize, process::ExitCode};
+
+use libadalang::gpr_project::GprProject;
+
+use server::Server;
+
+const USAGE: &str = "usage: libadalang-lsp [-P <project.gpr>] [-X<name>=<value>]... [-j<jobs>]";
+
+/// Command line options.
+pub struct Options {
+    project: Option<String>,
+    scenario_vars: Vec<(String, String)>,
+    /// Number of threads to index the project
+    jobs: NonZeroUsize,
+}
+
+impl Options {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut res = Self {
+            project: None,
+            scenario_vars: Vec::new(),
+            jobs: std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if let Some(project) = arg.strip_prefix("-P") {
+                res.project = Some(if project.is_empty() {
+                    args.next().ok_or("missing project file after -P")?
+                } else {
+                    project.to_owned()
+                });
+            } else if let Some(var) = arg.strip_prefix("-X") {
+                let (name, value) = var
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid scenario variable: {var}"))?;
+                res.scenario_vars.push((name.to_owned(), value.to_owned()));
+            } else if let Some(jobs) = arg.strip_prefix("-j") {
+                res.jobs = jobs
+                    .parse()
+                    .map_err(|_| format!("invalid number of jobs: {jobs}"))?;
+            } else {
+                return Err(format!("unexpected argument: {arg}"));
+            }
+        }
+
+        if res.project.is_none() && !res.scenario_vars.is_empty() {
+            return Err("scenario variables need a project file".to_owned());
+        }
+        Ok(res)
+    }
+
+    pub fn load_project(&self) -> libadalang::Result<GprProject> {
+        match &self.project {
+            Some(project) => GprProject::build(project)
+                .scenario_vars(
+                    self.scenario_vars
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str())),
+                )
+                .load(),
+            None => GprProject::build_implicit().load(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("libadalang-lsp: {msg}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let res = Server::new(options)
+        .map_err(|err| err.to_string())
+        .and_then(|server| server.run().map_err(|err| err.to_string()));
+    match res {
+        Ok(code) => code,
+        Err(msg) => {
+            eprintln!("libadalang-lsp: {msg}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! The subset of the LSP types used by the server, and conversions from libadalang types
+//!
+//! LSP positions are 0-based, with columns counted in UTF-16 code units. The server's context uses
+//! a tab stop of 1, so that libadalang columns count characters.
+
+use libadalang::diagnostic::{Diagnostic, Severity, SourceLocation, SourceRange};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TextDocumentIdentifier {
+    pub uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentItem {
+    pub uri: String,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidOpenParams {
+    pub text_document: TextDocumentItem,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentChange {
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidChangeParams {
+    pub text_document: TextDocumentIdentifier,
+    /// With full synchronization, the last change is the whole text
+    pub content_changes: Vec<ContentChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceContext {
+    pub include_declaration: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    #[serde(default)]
+    pub context: ReferenceContext,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LspDiagnostic {
+    pub range: Range,
+    pub severity: u8,
+    pub source: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarkupContent {
+    pub kind: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hover {
+    pub contents: MarkupContent,
+    pub range: Range,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: u8,
+    pub range: Range,
+    pub selection_range: Range,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DocumentSymbol>,
+}
+
+pub mod symbol_kind {
+    pub const PACKAGE: u8 = 4;
+    pub const CLASS: u8 = 5;
+    pub const METHOD: u8 = 6;
+    pub const FIELD: u8 = 8;
+    pub const FUNCTION: u8 = 12;
+    pub const VARIABLE: u8 = 13;
+    pub const CONSTANT: u8 = 14;
+    pub const ENUM_MEMBER: u8 = 22;
+    pub const STRUCT: u8 = 23;
+}
+
+/// The text of a source file, to convert positions.
+pub struct SourceText {
+    text: String,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl SourceText {
+    pub fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// Read the file `filename`, or use an empty text if it cannot be read, in which case
+    /// columns are used as-is.
+    pub fn read(filename: &str) -> Self {
+        Self::new(std::fs::read_to_string(filename).unwrap_or_default())
+    }
+
+    fn line(&self, line: u32) -> Option<&str> {
+        let start = *self.line_starts.get(line as usize)?;
+        let end = self
+            .line_starts
+            .get(line as usize + 1)
+            .map_or(self.text.len(), |end| end - 1);
+        Some(self.text[start..end].trim_end_matches('\r'))
+    }
+
+    pub fn position(&self, loc: SourceLocation) -> Position {
+        let line = loc.line.saturating_sub(1);
+        let column = usize::from(loc.column.saturating_sub(1));
+        let character = match self.line(line) {
+            Some(text) => text.chars().take(column).map(char::len_utf16).sum(),
+            None => column,
+        };
+        Position {
+            line,
+            character: character as u32,
+        }
+    }
+
+    pub fn range(&self, sloc_range: SourceRange) -> Range {
+        Range {
+            start: self.position(sloc_range.start),
+            end: self.position(sloc_range.end),
+        }
+    }
+
+    pub fn source_location(&self, pos: Position) -> SourceLocation {
+        let column = match self.line(pos.line) {
+            Some(text) => {
+                let mut units = 0;
+                text.chars()
+                    .take_while(|c| {
+                        units += c.len_utf16();
+                        units <= pos.character as usize
+                    })
+                    .count()
+            }
+            None => pos.character as usize,
+        };
+        SourceLocation::new(pos.line + 1, u16::try_from(column + 1).unwrap_or(u16::MAX))
+    }
+
+    pub fn diagnostic(&self, diag: &Diagnostic) -> LspDiagnostic {
+        LspDiagnostic {
+            range: self.range(diag.sloc_range),
+            severity: match diag.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            },
+            source: "libadalang",
+            message: diag.message.clone(),
+        }
+    }
+}
+
+/// Return the path of a `file://` URI.
+pub fn uri_to_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut res = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let hex = path
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (path[i], hex) {
+            (b'%', Some(byte)) => {
+                res.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                res.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(res).ok()
+}
+
+/// Return the `file://` URI of an absolute path.
+pub fn path_to_uri(path: &str) -> String {
+    let mut res = String::from("file://");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            res.push(char::from(byte));
+        } else {
+            res.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn positions() {
+        // "é" is one UTF-16 unit, "😀" is two
+        let text = SourceText::new("A := 1;\r\nB := \"é😀x\";\nC\n".to_owned());
+        let cases = [
+            (SourceLocation::new(1, 1), position(0, 0)),
+            (SourceLocation::new(1, 8), position(0, 7)),
+            (SourceLocation::new(2, 7), position(1, 6)),
+            (SourceLocation::new(2, 8), position(1, 7)),
+            (SourceLocation::new(2, 9), position(1, 9)),
+            (SourceLocation::new(2, 11), position(1, 11)),
+            (SourceLocation::new(3, 2), position(2, 1)),
+        ];
+        for (loc, pos) in cases {
+            let res = text.position(loc);
+            assert_eq!(
+                (res.line, res.character),
+                (pos.line, pos.character),
+                "{loc:?}"
+            );
+            assert_eq!(text.source_location(pos), loc, "{pos:?}");
+        }
+        // past the end of the line or of the text, columns are used as-is
+        assert_eq!(text.position(SourceLocation::new(5, 4)).character, 3);
+        assert_eq!(
+            text.source_location(position(4, 3)),
+            SourceLocation::new(5, 4)
+        );
+        assert_eq!(
+            text.source_location(position(0, 20)),
+            SourceLocation::new(1, 8)
+        );
+    }
+
+    #[test]
+    fn uris() {
+        let path = "/home/user/my project/pkg-é.ads";
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/user/my%20project/pkg-%C3%A9.ads");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        // lowercase escapes and unescaped characters
+        assert_eq!(
+            uri_to_path("file:///a%c3%a9/b:c").as_deref(),
+            Some("/aé/b:c")
+        );
+        // invalid escapes are kept
+        assert_eq!(uri_to_path("file:///a%zz%4").as_deref(), Some("/a%zz%4"));
+        assert_eq!(uri_to_path("file:///%FF"), None);
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+}
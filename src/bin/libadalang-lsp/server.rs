@@ -0,0 +1,447 @@
+//! Handling of the LSP requests and notifications
+
+use std::{collections::HashMap, io, num::NonZeroU8, process::ExitCode, rc::Rc};
+
+use libadalang::{
+    analysis::{Context, GrammarRule, Node, Unit, node::BaseKind},
+    driver::ProjectAnalyzer,
+    introspection::Value,
+    xref::{self, XrefIndex},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    Options,
+    protocol::{
+        DidChangeParams, DidOpenParams, DocumentParams, DocumentSymbol, Hover, Location,
+        MarkupContent, Position, PositionParams, ReferenceParams, SourceText, path_to_uri,
+        symbol_kind, uri_to_path,
+    },
+    transport::{
+        Connection, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, Message, ResponseError,
+        SERVER_NOT_INITIALIZED,
+    },
+};
+
+type Result<T, E = ResponseError> = std::result::Result<T, E>;
+
+fn parse<T: for<'de> Deserialize<'de>>(params: serde_json::Value) -> Result<T> {
+    Ok(serde_json::from_value(params)?)
+}
+
+fn to_value(value: impl Serialize) -> serde_json::Value {
+    serde_json::to_value(value).expect("LSP types are always serializable")
+}
+
+fn filename(uri: &str) -> Result<String> {
+    uri_to_path(uri)
+        .ok_or_else(|| ResponseError::new(INVALID_PARAMS, format!("unsupported URI: {uri}")))
+}
+
+fn source_text(documents: &HashMap<String, Rc<SourceText>>, filename: &str) -> Rc<SourceText> {
+    match documents.get(filename) {
+        Some(source) => source.clone(),
+        None => Rc::new(SourceText::read(filename)),
+    }
+}
+
+pub struct Server {
+    options: Options,
+    connection: Connection,
+    context: Context,
+    /// Text of the open documents, by filename
+    documents: HashMap<String, Rc<SourceText>>,
+    /// Cross-references of the project, built on the first references request
+    xrefs: Option<XrefIndex>,
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new(options: Options) -> libadalang::Result<Self> {
+        // with a tab stop of 1, columns count characters
+        let context = Context::build(options.load_project()?)
+            .tab_stop(NonZeroU8::MIN)
+            .finish()?;
+        Ok(Self {
+            options,
+            connection: Connection::stdio(),
+            context,
+            documents: HashMap::new(),
+            xrefs: None,
+            initialized: false,
+            shutdown: false,
+        })
+    }
+
+    /// Serve the client until the `exit` notification or the end of the input, and return the
+    /// exit code.
+    pub fn run(mut self) -> io::Result<ExitCode> {
+        while let Some(message) = self.connection.receive()? {
+            match message {
+                Message::Request { id, method, params } => {
+                    let result = self.handle_request(&method, params);
+                    self.connection.respond(id, result)?;
+                }
+                Message::Notification { method, .. } if method == "exit" => {
+                    return Ok(if self.shutdown {
+                        ExitCode::SUCCESS
+                    } else {
+                        ExitCode::FAILURE
+                    });
+                }
+                Message::Notification { method, params } => {
+                    if let Err(err) = self.handle_notification(&method, params) {
+                        self.log(&format!("{method}: {}", err.message))?;
+                    }
+                }
+            }
+        }
+        Ok(ExitCode::FAILURE)
+    }
+
+    /// Show an error message in the client's log.
+    fn log(&mut self, message: &str) -> io::Result<()> {
+        self.connection.notify(
+            "window/logMessage",
+            json!({ "type": 1, "message": message }),
+        )
+    }
+
+    fn handle_request(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                return Ok(json!({
+                    "capabilities": {
+                        "positionEncoding": "utf-16",
+                        "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "hoverProvider": true,
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": {
+                        "name": "libadalang-lsp",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }));
+            }
+            _ if !self.initialized => {
+                return Err(ResponseError::new(
+                    SERVER_NOT_INITIALIZED,
+                    "the server is not initialized",
+                ));
+            }
+            _ if self.shutdown => {
+                return Err(ResponseError::new(
+                    INVALID_REQUEST,
+                    "the server is shutting down",
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(match method {
+            "shutdown" => {
+                self.shutdown = true;
+                serde_json::Value::Null
+            }
+            "textDocument/definition" => to_value(self.definition(parse(params)?)?),
+            "textDocument/references" => to_value(self.references(parse(params)?)?),
+            "textDocument/hover" => to_value(self.hover(parse(params)?)?),
+            "textDocument/documentSymbol" => to_value(self.document_symbols(parse(params)?)?),
+            _ => {
+                return Err(ResponseError::new(
+                    METHOD_NOT_FOUND,
+                    format!("unsupported method: {method}"),
+                ));
+            }
+        })
+    }
+
+    fn handle_notification(&mut self, method: &str, params: serde_json::Value) -> Result<()> {
+        match method {
+            "textDocument/didOpen" => {
+                let params: DidOpenParams = parse(params)?;
+                self.set_text(&params.text_document.uri, params.text_document.text)
+            }
+            "textDocument/didChange" => {
+                let params: DidChangeParams = parse(params)?;
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.set_text(&params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            "textDocument/didSave" => {
+                let params: DocumentParams = parse(params)?;
+                let filename = filename(&params.text_document.uri)?;
+                if let Some(xrefs) = &mut self.xrefs {
+                    let unit = self.context.get_unit_from_file(
+                        &filename,
+                        "",
+                        false,
+                        GrammarRule::COMPILATION,
+                    )?;
+                    xrefs.update_unit(&unit)?;
+                }
+                Ok(())
+            }
+            "textDocument/didClose" => {
+                let params: DocumentParams = parse(params)?;
+                let filename = filename(&params.text_document.uri)?;
+                self.documents.remove(&filename);
+                // clear the diagnostics first, as reparsing may fail
+                self.connection.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": params.text_document.uri, "diagnostics": [] }),
+                )?;
+                // go back to the content of the file
+                self.context
+                    .get_unit_from_file(&filename, "", true, GrammarRule::COMPILATION)?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Parse the new text of the document `uri`, and publish its diagnostics.
+    fn set_text(&mut self, uri: &str, text: String) -> Result<()> {
+        let filename = filename(uri)?;
+        let unit =
+            self.context
+                .get_unit_from_buffer(&filename, "", &text, GrammarRule::COMPILATION)?;
+        let source = SourceText::new(text);
+
+        let diagnostics = unit
+            .diagnostics()
+            .map(|diag| source.diagnostic(&diag))
+            .collect::<Vec<_>>();
+        self.documents.insert(filename, Rc::new(source));
+        self.connection.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )?;
+        Ok(())
+    }
+
+    /// Return the unit of `filename`, with the text of the document if it is open.
+    fn unit(&self, filename: &str) -> Result<Unit> {
+        Ok(self
+            .context
+            .get_unit_from_file(filename, "", false, GrammarRule::COMPILATION)?)
+    }
+
+    /// Return the text of `filename`, from the document if it is open or else from the file.
+    fn source(&self, filename: &str) -> Rc<SourceText> {
+        source_text(&self.documents, filename)
+    }
+
+    /// Return the identifier at `position` in `filename`, and the text of the file.
+    fn id_at(&self, filename: &str, position: Position) -> Result<Option<(Node, Rc<SourceText>)>> {
+        let source = self.source(filename);
+        let loc = source.source_location(position);
+        let unit = self.unit(filename)?;
+
+        // nodes are visited in prefix order, so the last one is the innermost. The end of the
+        // range is included, for a cursor right after the identifier.
+        let Some(root) = unit.root() else {
+            return Ok(None);
+        };
+        let id = root
+            .traverse()?
+            .filter(|node| {
+                node.is_a(BaseKind::BASE_ID)
+                    && node
+                        .sloc_range()
+                        .is_ok_and(|range| range.start <= loc && loc <= range.end)
+            })
+            .last();
+        Ok(id.map(|id| (id, source)))
+    }
+
+    fn location(&self, node: &Node) -> Result<Location> {
+        let filename = node.unit().filename();
+        Ok(Location {
+            range: self.source(&filename).range(node.sloc_range()?),
+            uri: path_to_uri(&filename),
+        })
+    }
+
+    fn definition(&mut self, params: PositionParams) -> Result<Option<Location>> {
+        let filename = filename(&params.text_document.uri)?;
+        let Some((id, _)) = self.id_at(&filename, params.position)? else {
+            return Ok(None);
+        };
+        let Some(name) = xref::resolve(&id) else {
+            return Ok(None);
+        };
+
+        // on the defining name itself, go to the previous part, e.g. from a body to its spec
+        let is_self = name.unit().as_raw() == id.unit().as_raw()
+            && name.sloc_range()?.contains_range(&id.sloc_range()?);
+        let target = match name.eval("p_previous_part", &[]) {
+            Ok(Value::Node(Some(previous))) if is_self => previous,
+            _ => name,
+        };
+        Ok(Some(self.location(&target)?))
+    }
+
+    fn references(&mut self, params: ReferenceParams) -> Result<Vec<Location>> {
+        let filename = filename(&params.text_document.uri)?;
+        let mut xrefs = match self.xrefs.take() {
+            Some(xrefs) => xrefs,
+            None => {
+                // the locations of the index must use the tab stop of the server's context
+                let analyzer = ProjectAnalyzer::new(self.options.load_project()?)
+                    .jobs(self.options.jobs)
+                    .tab_stop(self.context.tab_stop());
+                let xrefs = XrefIndex::from_analyzer(analyzer)?;
+                for (filename, err) in xrefs.skipped_files() {
+                    self.log(&format!("cannot index {filename}: {err}"))?;
+                }
+                xrefs
+            }
+        };
+        // the index is built from the files, reindex the documents that were edited since
+        let updated = self
+            .documents
+            .keys()
+            .try_for_each(|filename| -> Result<()> {
+                xrefs.update_unit(&self.unit(filename)?)?;
+                Ok(())
+            });
+        let xrefs = self.xrefs.insert(xrefs);
+        updated?;
+
+        let loc = source_text(&self.documents, &filename).source_location(params.position);
+        let Some((decl, refs)) = xrefs.find_references(&filename, loc) else {
+            return Ok(Vec::new());
+        };
+
+        let mut sources = HashMap::new();
+        let locations = params
+            .context
+            .include_declaration
+            .then_some(&decl)
+            .into_iter()
+            .chain(refs);
+        Ok(locations
+            .map(|loc| {
+                let source = sources
+                    .entry(loc.filename.as_str())
+                    .or_insert_with(|| source_text(&self.documents, &loc.filename));
+                Location {
+                    uri: path_to_uri(&loc.filename),
+                    range: source.range(loc.sloc_range),
+                }
+            })
+            .collect())
+    }
+
+    fn hover(&mut self, params: PositionParams) -> Result<Option<Hover>> {
+        let filename = filename(&params.text_document.uri)?;
+        let Some((id, source)) = self.id_at(&filename, params.position)? else {
+            return Ok(None);
+        };
+        let Some(name) = xref::resolve(&id) else {
+            return Ok(None);
+        };
+        let Ok(Value::Node(Some(decl))) = name.eval("p_basic_decl", &[]) else {
+            return Ok(None);
+        };
+
+        // the profile of subprograms, or the first line of other declarations
+        let summary = match decl.field("f_subp_spec")? {
+            Some(Some(spec)) => spec.text()?,
+            _ => decl.text()?.lines().next().unwrap_or_default().to_owned(),
+        };
+        let mut value = format!("```ada\n{summary}\n```");
+        if let Ok(Value::String(name)) = decl.eval("p_fully_qualified_name", &[]) {
+            value.push_str(&format!("\n\n`{name}`"));
+        }
+
+        Ok(Some(Hover {
+            contents: MarkupContent {
+                kind: "markdown",
+                value,
+            },
+            range: source.range(id.sloc_range()?),
+        }))
+    }
+
+    fn document_symbols(&mut self, params: DocumentParams) -> Result<Vec<DocumentSymbol>> {
+        let filename = filename(&params.text_document.uri)?;
+        let source = self.source(&filename);
+        let mut res = Vec::new();
+        if let Some(root) = self.unit(&filename)?.root() {
+            document_symbols(&root, &source, &mut res)?;
+        }
+        Ok(res)
+    }
+}
+
+/// Return the symbol kind of declarations of the kind `kind_name`, or `None` for declarations
+/// that are not listed as symbols.
+fn symbol_kind(kind_name: &str) -> Option<u8> {
+    Some(match kind_name {
+        "ParamSpec"
+        | "DiscriminantSpec"
+        | "AnonymousTypeDecl"
+        | "GenericSubpInternal"
+        | "GenericPackageInternal"
+        | "ForLoopVarDecl"
+        | "ExtendedReturnStmtObjectDecl"
+        | "EntryIndexSpec"
+        | "LabelDecl"
+        | "NamedStmtDecl"
+        | "ExceptionHandler" => return None,
+        "EnumLiteralDecl" => symbol_kind::ENUM_MEMBER,
+        "ComponentDecl" => symbol_kind::FIELD,
+        "EntryDecl" | "EntryBody" => symbol_kind::METHOD,
+        "NumberDecl" | "ExceptionDecl" => symbol_kind::CONSTANT,
+        "ObjectDecl" => symbol_kind::VARIABLE,
+        name if name.contains("Package") => symbol_kind::PACKAGE,
+        name if name.contains("Subp") || name == "ExprFunction" => symbol_kind::FUNCTION,
+        name if name.contains("Task") || name.contains("Protected") => symbol_kind::CLASS,
+        name if name.contains("TypeDecl") => symbol_kind::STRUCT,
+        _ => symbol_kind::VARIABLE,
+    })
+}
+
+/// Collect the declarations below `node`, as a hierarchy of symbols.
+fn document_symbols(
+    node: &Node,
+    source: &SourceText,
+    res: &mut Vec<DocumentSymbol>,
+) -> libadalang::Result<()> {
+    for child in node.children()? {
+        if child.is_a(BaseKind::BASIC_DECL)
+            && let Some(kind) = symbol_kind(&child.kind_name())
+            && let Ok(Value::Nodes(names)) = child.eval("p_defining_names", &[])
+        {
+            let mut children = Vec::new();
+            document_symbols(&child, source, &mut children)?;
+            let range = source.range(child.sloc_range()?);
+            // nested declarations go to the first name of declarations like `A, B : T;`
+            let mut children = Some(children);
+            for name in names.into_iter().flatten() {
+                res.push(DocumentSymbol {
+                    name: name.text()?,
+                    kind,
+                    range,
+                    selection_range: source.range(name.sloc_range()?),
+                    children: children.take().unwrap_or_default(),
+                });
+            }
+        } else {
+            document_symbols(&child, source, res)?;
+        }
+    }
+    Ok(())
+}
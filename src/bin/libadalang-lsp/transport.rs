@@ -0,0 +1,159 @@
+//! JSON-RPC messages over stdio, framed by `Content-Length` headers
+
+use std::io::{self, BufRead, BufReader, Read, StdinLock, StdoutLock, Write};
+
+use serde_json::{Value, json};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+pub const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// An incoming message. Responses to requests of the server are ignored, as it sends none.
+#[derive(Debug)]
+pub enum Message {
+    Request {
+        id: Value,
+        method: String,
+        params: Value,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+}
+
+/// An error sent in response to a request.
+#[derive(Debug)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<libadalang::Error> for ResponseError {
+    fn from(err: libadalang::Error) -> Self {
+        Self::new(INTERNAL_ERROR, err.to_string())
+    }
+}
+
+impl From<io::Error> for ResponseError {
+    fn from(err: io::Error) -> Self {
+        Self::new(INTERNAL_ERROR, err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ResponseError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::new(INVALID_PARAMS, err.to_string())
+    }
+}
+
+/// A connection to the client, on the standard input and output.
+pub struct Connection {
+    input: BufReader<StdinLock<'static>>,
+    output: StdoutLock<'static>,
+}
+
+impl Connection {
+    pub fn stdio() -> Self {
+        Self {
+            input: BufReader::new(io::stdin().lock()),
+            output: io::stdout().lock(),
+        }
+    }
+
+    /// Read the next request or notification, or `None` at the end of the input.
+    ///
+    /// Messages whose body is not valid JSON are answered with a parse error and skipped.
+    pub fn receive(&mut self) -> io::Result<Option<Message>> {
+        loop {
+            let value = match self.read_value()? {
+                None => return Ok(None),
+                Some(Ok(value)) => value,
+                Some(Err(err)) => {
+                    // the id of the request is unknown
+                    let err = ResponseError::new(PARSE_ERROR, err.to_string());
+                    self.respond(Value::Null, Err(err))?;
+                    continue;
+                }
+            };
+            let Some(method) = value.get("method").and_then(Value::as_str) else {
+                continue;
+            };
+            let method = method.to_owned();
+            let params = value.get("params").cloned().unwrap_or(Value::Null);
+            return Ok(Some(match value.get("id") {
+                Some(id) => Message::Request {
+                    id: id.clone(),
+                    method,
+                    params,
+                },
+                None => Message::Notification { method, params },
+            }));
+        }
+    }
+
+    /// Read the body of the next message, or `None` at the end of the input. The body is parsed
+    /// separately, as a malformed body leaves the input at the start of the next message.
+    fn read_value(&mut self) -> io::Result<Option<serde_json::Result<Value>>> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                let value = value.trim().parse::<usize>();
+                length =
+                    Some(value.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?);
+            }
+        }
+
+        let length = length.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+        })?;
+        let mut body = vec![0; length];
+        self.input.read_exact(&mut body)?;
+        Ok(Some(serde_json::from_slice(&body)))
+    }
+
+    fn send(&mut self, value: &Value) -> io::Result<()> {
+        let body = value.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+
+    /// Send the response to the request `id`.
+    pub fn respond(&mut self, id: Value, result: Result<Value, ResponseError>) -> io::Result<()> {
+        self.send(&match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            }),
+        })
+    }
+
+    /// Send a notification to the client.
+    pub fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+}
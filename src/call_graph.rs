@@ -0,0 +1,579 @@
+//! Call graph of the subprograms of a project
+//!
+//! For each subprogram, task or entry body, the calls it contains (in nested packages and blocks
+//! too, but not in nested bodies of these kinds) are resolved with `p_referenced_decl`.
+//! Dispatching calls are flagged with `p_is_dispatching_call`, and the subprograms that override
+//! their target are found with `p_base_subp_declarations`, so that [`CallGraph::reachable_from`]
+//! follows every possible target of a dispatch.
+//!
+//! Subprograms are identified by the [`Location`] of the defining name of their declaration: the
+//! specification if there is one, the body otherwise. Tasks and entries are included as
+//! subprograms, as callers. Calls done during the elaboration of packages are not included.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    num::NonZeroUsize,
+};
+
+use crate::{
+    Result,
+    analysis::{Node, Unit, node::BaseKind},
+    diagnostic::SourceRange,
+    driver::analyze_project,
+    gpr_project::GprProject,
+    introspection::Value,
+    xref::Location,
+};
+
+/// A subprogram of the call graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subprogram {
+    /// Fully qualified name, e.g. `Pkg.Proc`
+    pub name: String,
+    /// Location of the defining name of the declaration, which identifies the subprogram
+    pub location: Location,
+    /// Location of the defining name of the body, if it was analyzed
+    pub body: Option<Location>,
+}
+
+/// A call from a subprogram body.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Call {
+    pub caller: Location,
+    /// The called subprogram. For a dispatching call, this is the primitive of the static type.
+    pub callee: Location,
+    /// Location of the name of the called subprogram in the call
+    pub location: Location,
+    pub is_dispatching: bool,
+}
+
+/// The subprograms and calls of a unit, as found by [`CallGraph::collect_unit`].
+#[derive(Debug, Clone, Default)]
+pub struct UnitCalls {
+    subprograms: Vec<Subprogram>,
+    calls: Vec<Call>,
+    /// (overridden subprogram, overriding subprogram)
+    overrides: Vec<(Location, Location)>,
+}
+
+/// Return the location of the defining name of `decl`.
+fn defining_location(decl: &Node) -> Option<Location> {
+    match decl.eval("p_defining_name", &[]) {
+        Ok(Value::Node(Some(name))) => Location::of_node(&name).ok(),
+        _ => None,
+    }
+}
+
+fn bool_property(node: &Node, name: &str) -> bool {
+    matches!(node.eval(name, &[]), Ok(Value::Bool(true)))
+}
+
+fn subprogram(decl: &Node, body: Option<&Node>) -> Option<Subprogram> {
+    let name = match decl.eval("p_fully_qualified_name", &[]) {
+        Ok(Value::String(name)) => name,
+        _ => decl.text().ok()?,
+    };
+    Some(Subprogram {
+        name,
+        location: defining_location(decl)?,
+        body: body.and_then(defining_location),
+    })
+}
+
+/// Return the specification of a subprogram body, or the body itself if it has none.
+fn canonical_decl(body: &Node) -> Node {
+    match body.eval("p_decl_part", &[]) {
+        Ok(Value::Node(Some(decl))) => decl,
+        _ => *body,
+    }
+}
+
+/// Whether `node` is a body whose statements run when it is called or activated: a subprogram,
+/// task or entry body.
+fn is_callable_body(node: &Node) -> bool {
+    node.is_a(BaseKind::BASE_SUBP_BODY)
+        || node.is_a(BaseKind::TASK_BODY)
+        || node.is_a(BaseKind::ENTRY_BODY)
+}
+
+/// Return the innermost subprogram, task or entry body that contains `node`.
+fn enclosing_body(node: &Node) -> Option<Node> {
+    let mut current = node.parent().ok()??;
+    loop {
+        if is_callable_body(&current) {
+            return Some(current);
+        }
+        current = current.parent().ok()??;
+    }
+}
+
+/// A graph of the calls between subprograms.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    subprograms: BTreeMap<Location, Subprogram>,
+    /// Calls, sorted by caller
+    calls: Vec<Call>,
+    /// Subprograms overriding each subprogram
+    overrides: BTreeMap<Location, BTreeSet<Location>>,
+}
+
+impl CallGraph {
+    /// Create an empty call graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the call graph of the source files of `project`, parsed and resolved on `jobs`
+    /// threads.
+    pub fn from_project(project: GprProject, jobs: NonZeroUsize) -> Result<Self> {
+        let analyses = analyze_project(project, jobs, |unit| Self::collect_unit(&unit))?;
+
+        let mut res = Self::new();
+        for analysis in analyses {
+            if let Ok(calls) = analysis.result {
+                res.insert(calls);
+            }
+        }
+        res.sort_calls();
+        Ok(res)
+    }
+
+    /// Find the subprogram bodies and the calls of `unit`, to [add](CallGraph::add) them to a
+    /// graph. Calls that cannot be resolved are skipped.
+    ///
+    /// The result does not depend on the context of `unit`, so this can be done on another
+    /// thread than the one building the graph.
+    pub fn collect_unit(unit: &Unit) -> UnitCalls {
+        let mut res = UnitCalls::default();
+        // subprograms of the bodies of this unit, by range of the body
+        let mut bodies = HashMap::<SourceRange, Option<Location>>::new();
+
+        let nodes = unit
+            .root()
+            .into_iter()
+            .flat_map(|root| root.traverse())
+            .flatten();
+        for node in nodes {
+            if is_callable_body(&node) {
+                let decl = canonical_decl(&node);
+                let Some(subp) = subprogram(&decl, Some(&node)) else {
+                    continue;
+                };
+                if let Ok(Value::Nodes(bases)) = decl.eval("p_base_subp_declarations", &[]) {
+                    res.overrides.extend(
+                        bases
+                            .iter()
+                            .flatten()
+                            .filter_map(defining_location)
+                            .filter(|base| *base != subp.location)
+                            .map(|base| (base, subp.location.clone())),
+                    );
+                }
+                if let Ok(sloc_range) = node.sloc_range() {
+                    bodies.insert(sloc_range, Some(subp.location.clone()));
+                }
+                res.subprograms.push(subp);
+                continue;
+            }
+
+            if !node.is_a(BaseKind::BASE_ID) || !bool_property(&node, "p_is_call") {
+                continue;
+            }
+            let Some(body) = enclosing_body(&node) else {
+                continue;
+            };
+            let Ok(body_range) = body.sloc_range() else {
+                continue;
+            };
+            let caller = bodies
+                .entry(body_range)
+                .or_insert_with(|| defining_location(&canonical_decl(&body)));
+            let Some(caller) = caller.clone() else {
+                continue;
+            };
+            let Ok(Value::Node(Some(callee))) = node.eval("p_referenced_decl", &[]) else {
+                continue;
+            };
+            let Some(callee) = subprogram(&callee, None) else {
+                continue;
+            };
+            let Ok(location) = Location::of_node(&node) else {
+                continue;
+            };
+
+            res.calls.push(Call {
+                caller,
+                callee: callee.location.clone(),
+                location,
+                is_dispatching: bool_property(&node, "p_is_dispatching_call"),
+            });
+            res.subprograms.push(callee);
+        }
+
+        res
+    }
+
+    /// Add the subprograms and calls of a unit to the graph.
+    pub fn add(&mut self, calls: UnitCalls) {
+        self.insert(calls);
+        self.sort_calls();
+    }
+
+    /// Add the subprograms and calls of a unit, leaving the calls unsorted.
+    fn insert(&mut self, calls: UnitCalls) {
+        for subp in calls.subprograms {
+            match self.subprograms.get_mut(&subp.location) {
+                Some(known) => {
+                    if known.body.is_none() {
+                        known.body = subp.body;
+                    }
+                }
+                None => {
+                    self.subprograms.insert(subp.location.clone(), subp);
+                }
+            }
+        }
+        for (base, overriding) in calls.overrides {
+            self.overrides.entry(base).or_default().insert(overriding);
+        }
+        self.calls.extend(calls.calls);
+    }
+
+    fn sort_calls(&mut self) {
+        self.calls.sort();
+        self.calls.dedup();
+    }
+
+    /// Add the subprograms and calls of `unit` to the graph.
+    pub fn add_unit(&mut self, unit: &Unit) {
+        self.add(Self::collect_unit(unit));
+    }
+
+    /// Iterate over the subprograms of the graph, sorted by location.
+    pub fn subprograms(&self) -> impl Iterator<Item = &Subprogram> {
+        self.subprograms.values()
+    }
+
+    /// Return the subprogram declared at `location`.
+    pub fn subprogram(&self, location: &Location) -> Option<&Subprogram> {
+        self.subprograms.get(location)
+    }
+
+    /// Iterate over all the calls of the graph, sorted by caller.
+    pub fn calls(&self) -> impl Iterator<Item = &Call> {
+        self.calls.iter()
+    }
+
+    /// Return the calls done by the subprogram `caller`.
+    pub fn calls_from(&self, caller: &Location) -> &[Call] {
+        let start = self.calls.partition_point(|call| call.caller < *caller);
+        let end = self.calls.partition_point(|call| call.caller <= *caller);
+        &self.calls[start..end]
+    }
+
+    /// Return the calls to the subprogram `callee`, dispatching calls to the subprograms it
+    /// overrides included.
+    pub fn calls_to(&self, callee: &Location) -> Vec<&Call> {
+        self.calls
+            .iter()
+            .filter(|call| self.targets(call).contains(&callee))
+            .collect()
+    }
+
+    /// Return the subprograms that override `subp`, directly or not.
+    pub fn overriding(&self, subp: &Location) -> Vec<&Location> {
+        self.overrides
+            .get(subp)
+            .map(|overriding| overriding.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return the subprograms that may be called by `call`: its callee, and for a dispatching
+    /// call the subprograms overriding it.
+    pub fn targets<'a>(&'a self, call: &'a Call) -> Vec<&'a Location> {
+        let mut res = vec![&call.callee];
+        if call.is_dispatching {
+            res.extend(self.overriding(&call.callee));
+        }
+        res
+    }
+
+    /// Return the subprograms that may be called, directly or not, by `subp`, sorted. The
+    /// subprogram itself is only included if it is recursive.
+    pub fn reachable_from(&self, subp: &Location) -> BTreeSet<&Location> {
+        let mut res = BTreeSet::new();
+        let mut stack = vec![subp];
+        while let Some(caller) = stack.pop() {
+            for call in self.calls_from(caller) {
+                for target in self.targets(call) {
+                    if res.insert(target) {
+                        stack.push(target);
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Calls aggregated by caller, callee and dispatching flag, with their count.
+    fn edges(&self) -> BTreeMap<(&Location, &Location, bool), usize> {
+        let mut res = BTreeMap::new();
+        for call in &self.calls {
+            *res.entry((&call.caller, &call.callee, call.is_dispatching))
+                .or_default() += 1;
+        }
+        res
+    }
+
+    /// Identifiers of the subprograms in exports.
+    fn node_ids(&self) -> HashMap<&Location, usize> {
+        self.subprograms
+            .keys()
+            .enumerate()
+            .map(|(i, location)| (location, i))
+            .collect()
+    }
+
+    /// Export the graph in the DOT format of Graphviz.
+    ///
+    /// Calls between the same subprograms are merged. Dispatching calls are dashed, and have an
+    /// edge to each overriding subprogram.
+    pub fn to_dot(&self) -> String {
+        let ids = self.node_ids();
+        let mut res = String::from("digraph calls {\n");
+        for (i, subp) in self.subprograms.values().enumerate() {
+            let _ = writeln!(
+                res,
+                "    n{i} [label=\"{}\", tooltip=\"{}\"];",
+                dot_escape(&subp.name),
+                dot_escape(&subp.location.to_string())
+            );
+        }
+        for ((caller, callee, is_dispatching), count) in self.edges() {
+            let style = if is_dispatching { ", style=dashed" } else { "" };
+            let _ = writeln!(
+                res,
+                "    n{} -> n{} [label=\"{count}\"{style}];",
+                ids[caller], ids[callee]
+            );
+            if is_dispatching {
+                for target in self.overriding(callee) {
+                    let _ = writeln!(
+                        res,
+                        "    n{} -> n{} [style=dotted];",
+                        ids[caller], ids[target]
+                    );
+                }
+            }
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Export the graph in the GraphML format.
+    ///
+    /// Nodes have a `name` and a `location`, and edges have a `dispatching` flag and the number
+    /// of `calls` they stand for. Dispatching calls also have an edge to each overriding
+    /// subprogram, with the `overriding` flag.
+    pub fn to_graphml(&self) -> String {
+        let ids = self.node_ids();
+        let mut res = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"location\" for=\"node\" attr.name=\"location\" attr.type=\"string\"/>\n",
+            "  <key id=\"dispatching\" for=\"edge\" attr.name=\"dispatching\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"calls\" for=\"edge\" attr.name=\"calls\" attr.type=\"int\"/>\n",
+            "  <key id=\"overriding\" for=\"edge\" attr.name=\"overriding\" attr.type=\"boolean\">\n",
+            "    <default>false</default>\n",
+            "  </key>\n",
+            "  <graph id=\"calls\" edgedefault=\"directed\">\n",
+        ));
+        for (i, subp) in self.subprograms.values().enumerate() {
+            let _ = writeln!(
+                res,
+                "    <node id=\"n{i}\"><data key=\"name\">{}</data><data key=\"location\">{}</data></node>",
+                xml_escape(&subp.name),
+                xml_escape(&subp.location.to_string())
+            );
+        }
+        for ((caller, callee, is_dispatching), count) in self.edges() {
+            let _ = writeln!(
+                res,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"dispatching\">{is_dispatching}</data><data key=\"calls\">{count}</data></edge>",
+                ids[caller], ids[callee]
+            );
+            if is_dispatching {
+                for target in self.overriding(callee) {
+                    let _ = writeln!(
+                        res,
+                        "    <edge source=\"n{}\" target=\"n{}\"><data key=\"dispatching\">true</data><data key=\"calls\">{count}</data><data key=\"overriding\">true</data></edge>",
+                        ids[caller], ids[target]
+                    );
+                }
+            }
+        }
+        res.push_str("  </graph>\n</graphml>\n");
+        res
+    }
+
+    /// Serialize the graph to a JSON string, with the list of subprograms, the list of calls,
+    /// and the overriding subprograms of each subprogram.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("call graphs are always serializable")
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use serde::Serialize;
+
+    use super::{Call, CallGraph, Subprogram};
+    use crate::xref::Location;
+
+    #[derive(Serialize)]
+    struct Graph<'a> {
+        subprograms: Vec<&'a Subprogram>,
+        calls: &'a [Call],
+        overrides: Vec<Override<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Override<'a> {
+        subprogram: &'a Location,
+        overriding: Vec<&'a Location>,
+    }
+
+    impl Serialize for CallGraph {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Graph {
+                subprograms: self.subprograms.values().collect(),
+                calls: &self.calls,
+                overrides: self
+                    .overrides
+                    .iter()
+                    .map(|(subprogram, overriding)| Override {
+                        subprogram,
+                        overriding: overriding.iter().collect(),
+                    })
+                    .collect(),
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::SourceLocation;
+
+    fn location(line: u32) -> Location {
+        let start = SourceLocation::new(line, 1);
+        Location::new("p.adb", SourceRange::new(start, start))
+    }
+
+    fn subprogram(name: &str, line: u32) -> Subprogram {
+        Subprogram {
+            name: name.into(),
+            location: location(line),
+            body: None,
+        }
+    }
+
+    fn call(caller: u32, callee: u32, line: u32, is_dispatching: bool) -> Call {
+        Call {
+            caller: location(caller),
+            callee: location(callee),
+            location: location(line),
+            is_dispatching,
+        }
+    }
+
+    /// `main` calls `a` twice and dispatches to `base`, overridden by `child`, which calls `a`.
+    fn graph() -> CallGraph {
+        let mut graph = CallGraph::new();
+        graph.add(UnitCalls {
+            subprograms: vec![
+                subprogram("main", 10),
+                subprogram("base", 20),
+                subprogram("child", 30),
+            ],
+            calls: vec![call(30, 40, 31, false), call(10, 20, 13, true)],
+            overrides: vec![(location(20), location(30))],
+        });
+        graph.add(UnitCalls {
+            subprograms: vec![subprogram("a", 40)],
+            calls: vec![call(10, 40, 12, false), call(10, 40, 11, false)],
+            overrides: Vec::new(),
+        });
+        graph
+    }
+
+    #[test]
+    fn calls() {
+        let graph = graph();
+        let callers = graph
+            .calls()
+            .map(|call| {
+                (
+                    call.caller.sloc_range.start.line,
+                    call.location.sloc_range.start.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        // sorted by caller, then callee
+        assert_eq!(callers, [(10, 13), (10, 11), (10, 12), (30, 31)]);
+        assert_eq!(graph.calls_from(&location(10)).len(), 3);
+        assert!(graph.calls_from(&location(40)).is_empty());
+
+        // the dispatching call may reach `child`
+        let calls_to = |line| graph.calls_to(&location(line)).len();
+        assert_eq!((calls_to(20), calls_to(30), calls_to(40)), (1, 1, 3));
+    }
+
+    #[test]
+    fn reachable_from() {
+        let graph = graph();
+        let reachable = graph.reachable_from(&location(10));
+        assert_eq!(
+            reachable.into_iter().cloned().collect::<Vec<_>>(),
+            [location(20), location(30), location(40)]
+        );
+        assert!(graph.reachable_from(&location(40)).is_empty());
+    }
+
+    #[test]
+    fn exports() {
+        let graph = graph();
+        let dot = graph.to_dot();
+        assert!(dot.contains("    n0 -> n3 [label=\"2\"];\n"));
+        assert!(dot.contains("    n0 -> n1 [label=\"1\", style=dashed];\n"));
+        assert!(dot.contains("    n0 -> n2 [style=dotted];\n"));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains(concat!(
+            "<edge source=\"n0\" target=\"n3\"><data key=\"dispatching\">false</data>",
+            "<data key=\"calls\">2</data></edge>"
+        )));
+        assert!(graphml.contains(concat!(
+            "<edge source=\"n0\" target=\"n2\"><data key=\"dispatching\">true</data>",
+            "<data key=\"calls\">1</data><data key=\"overriding\">true</data></edge>"
+        )));
+    }
+}
@@ -0,0 +1,765 @@
+//! Dependency graph of the units of a project
+//!
+//! Dependencies are found in the syntax tree of the units (see
+//! [`compilation_unit`](crate::analysis::compilation_unit)):
+//!
+//! - `with` clauses, which depend on the specification of the named unit, or on its body for
+//!   subprograms without specification,
+//! - child units, which depend on the specification of their parent unit,
+//! - bodies, which depend on their specification,
+//! - subunits, which depend on the body of their parent unit.
+//!
+//! The units designated by `with` clauses and the specifications of bodies are found by name
+//! resolution (`p_withed_units` and `p_decl_part`) when possible, and from the syntax otherwise,
+//! e.g. when the named units cannot be found.
+//!
+//! Units that are depended upon but were not added to the graph (e.g. runtime units when only the
+//! sources of the project are analyzed) are external: they have no filename and no dependencies.
+//!
+//! ```no_run
+//! # use std::num::NonZeroUsize;
+//! # use libadalang::{analysis::UnitKind, dependency_graph::{DependencyGraph, UnitId}, gpr_project::GprProject};
+//! # fn f() -> libadalang::Result<()> {
+//! let project = GprProject::build("main.gpr").load()?;
+//! let graph = DependencyGraph::from_project(project, NonZeroUsize::new(8).unwrap())?;
+//! for unit in graph.affected_by(&UnitId::new("utils", UnitKind::Spec)) {
+//!     println!("{unit} must be recompiled");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, fmt::Display, num::NonZeroUsize};
+
+use crate::{
+    Result,
+    analysis::{
+        Node, Unit,
+        compilation_unit::{CompilationUnit, UnitKind, WithedUnit},
+    },
+    driver::analyze_project,
+    gpr_project::GprProject,
+    introspection::Value,
+};
+
+/// Identifier of a unit in the graph: its fully qualified name, in lower case, and its kind.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitId {
+    pub name: String,
+    pub kind: UnitKind,
+}
+
+impl UnitId {
+    pub fn new(name: impl Into<String>, kind: UnitKind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+        }
+    }
+
+    fn spec(name: &str) -> Self {
+        Self::new(name, UnitKind::Spec)
+    }
+
+    fn body(name: &str) -> Self {
+        Self::new(name, UnitKind::Body)
+    }
+
+    /// Return the id of a compilation unit, or `None` if its name cannot be computed.
+    fn of_compilation_unit(unit: &CompilationUnit) -> Option<Self> {
+        Some(Self::new(unit.name().ok()?, unit.kind()))
+    }
+
+    /// Return the id of the compilation unit that contains `node`.
+    fn of_enclosing_unit(node: &Node) -> Option<Self> {
+        match node.eval("p_enclosing_compilation_unit", &[]) {
+            Ok(Value::Node(Some(unit))) => {
+                Self::of_compilation_unit(&CompilationUnit::from_node(unit)?)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for UnitId {
+    /// Format the unit as `name (spec)` or `name (body)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            UnitKind::Spec => "spec",
+            UnitKind::Body => "body",
+        };
+        write!(f, "{} ({kind})", self.name)
+    }
+}
+
+/// Why a unit depends on another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DependencyKind {
+    /// A `with` clause
+    With,
+    /// A `limited with` clause. It does not constrain the elaboration order, so it is ignored by
+    /// [`DependencyGraph::topological_order`] and [`DependencyGraph::cycles`].
+    LimitedWith,
+    /// A child unit depends on the specification of its parent
+    Parent,
+    /// A body depends on its specification
+    Body,
+    /// A subunit depends on the body of its parent
+    Separate,
+}
+
+impl DependencyKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::With => "with",
+            Self::LimitedWith => "limited with",
+            Self::Parent => "parent",
+            Self::Body => "body",
+            Self::Separate => "separate",
+        }
+    }
+}
+
+/// The dependencies of a compilation unit, as written in its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitDependencies {
+    pub id: UnitId,
+    pub filename: String,
+    pub withed_units: Vec<WithedUnit>,
+    /// Name of the parent unit of a subunit
+    pub separate_parent: Option<String>,
+    /// Units designated by the `with` clauses, found by name resolution. A `with` clause whose
+    /// name is not among them designates the specification of that name.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub resolved_withs: Vec<UnitId>,
+    /// Specification of a body, found by name resolution. Without it, a body depends on the
+    /// specification of the same name if it is in the graph.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spec: Option<UnitId>,
+}
+
+impl UnitDependencies {
+    /// Collect the dependencies of a compilation unit of the file `filename`.
+    pub fn from_compilation_unit(unit: &CompilationUnit, filename: &str) -> Result<Self> {
+        let id = UnitId::new(unit.name()?, unit.kind());
+        let spec = if id.kind == UnitKind::Body && !unit.is_subunit() {
+            resolved_spec(unit)
+        } else {
+            None
+        };
+        Ok(Self {
+            id,
+            filename: filename.to_owned(),
+            withed_units: unit.withed_units(),
+            separate_parent: unit.parent_name(),
+            resolved_withs: resolved_withs(unit),
+            spec,
+        })
+    }
+
+    /// Collect the dependencies of all the compilation units of `unit`.
+    pub fn from_unit(unit: &Unit) -> Result<Vec<Self>> {
+        let filename = unit.filename();
+        unit.compilation_units()
+            .iter()
+            .map(|cu| Self::from_compilation_unit(cu, &filename))
+            .collect()
+    }
+}
+
+/// Return the units designated by the `with` clauses of `unit`, private ones included.
+fn resolved_withs(unit: &CompilationUnit) -> Vec<UnitId> {
+    match unit.node().eval("p_withed_units", &[Value::Bool(true)]) {
+        Ok(Value::Nodes(units)) => units
+            .into_iter()
+            .flatten()
+            .filter_map(CompilationUnit::from_node)
+            .filter_map(|unit| UnitId::of_compilation_unit(&unit))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Return the specification of the body `unit`, or `None` for subprograms without one.
+fn resolved_spec(unit: &CompilationUnit) -> Option<UnitId> {
+    match unit.decl()?.eval("p_decl_part", &[]) {
+        Ok(Value::Node(Some(spec))) => UnitId::of_enclosing_unit(&spec),
+        _ => None,
+    }
+}
+
+/// Return the unit designated by the name of a `with` clause of `unit`: the specification of
+/// that name, or the body of a subprogram without specification.
+fn withed_id(
+    name: &str,
+    unit: &UnitDependencies,
+    deps: &HashMap<UnitId, UnitDependencies>,
+) -> UnitId {
+    let spec = UnitId::spec(name);
+    let body = UnitId::body(name);
+    let is_library_body = |id: &UnitId| {
+        deps.get(id)
+            .is_some_and(|unit| unit.separate_parent.is_none())
+    };
+    if unit.resolved_withs.contains(&spec) || deps.contains_key(&spec) {
+        spec
+    } else if unit.resolved_withs.contains(&body) || is_library_body(&body) {
+        body
+    } else {
+        spec
+    }
+}
+
+struct GraphUnit {
+    id: UnitId,
+    filename: Option<String>,
+    dependencies: Vec<(usize, DependencyKind)>,
+    dependents: Vec<(usize, DependencyKind)>,
+}
+
+/// A graph of dependencies between units.
+pub struct DependencyGraph {
+    /// Units sorted by id
+    units: Vec<GraphUnit>,
+    index: HashMap<UnitId, usize>,
+}
+
+impl DependencyGraph {
+    /// Build the graph of the given units. If a unit is given several times, the last one wins.
+    pub fn new(units: impl IntoIterator<Item = UnitDependencies>) -> Self {
+        let mut deps = units
+            .into_iter()
+            .map(|unit| (unit.id.clone(), unit))
+            .collect::<HashMap<_, _>>();
+
+        // list all the units first, including the external ones, to sort them by id
+        let mut edges = Vec::new();
+        for (id, unit) in &deps {
+            for withed in &unit.withed_units {
+                let kind = if withed.is_limited {
+                    DependencyKind::LimitedWith
+                } else {
+                    DependencyKind::With
+                };
+                edges.push((id.clone(), withed_id(&withed.name, unit, &deps), kind));
+            }
+
+            let spec = unit.spec.clone().unwrap_or_else(|| UnitId::spec(&id.name));
+            let has_spec = unit.spec.is_some() || deps.contains_key(&spec);
+            let is_library_unit = unit.separate_parent.is_none();
+            if let Some(parent) = &unit.separate_parent {
+                edges.push((id.clone(), UnitId::body(parent), DependencyKind::Separate));
+            } else if id.kind == UnitKind::Body && has_spec {
+                edges.push((id.clone(), spec, DependencyKind::Body));
+            }
+
+            // bodies with a specification get their parent through it
+            if let Some((parent, _)) = id.name.rsplit_once('.')
+                && is_library_unit
+                && (id.kind == UnitKind::Spec || !has_spec)
+            {
+                edges.push((id.clone(), UnitId::spec(parent), DependencyKind::Parent));
+            }
+        }
+
+        let mut ids = deps
+            .keys()
+            .cloned()
+            .chain(edges.iter().map(|(_, to, _)| to.clone()))
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let mut units = ids
+            .into_iter()
+            .map(|id| GraphUnit {
+                filename: deps.remove(&id).map(|unit| unit.filename),
+                id,
+                dependencies: Vec::new(),
+                dependents: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        edges.sort();
+        edges.dedup();
+        for (from, to, kind) in edges {
+            let (from, to) = (index[&from], index[&to]);
+            units[from].dependencies.push((to, kind));
+            units[to].dependents.push((from, kind));
+        }
+
+        Self { units, index }
+    }
+
+    /// Build the graph of the compilation units of `units`.
+    pub fn from_units<'a>(units: impl IntoIterator<Item = &'a Unit>) -> Result<Self> {
+        let mut deps = Vec::new();
+        for unit in units {
+            deps.extend(UnitDependencies::from_unit(unit)?);
+        }
+        Ok(Self::new(deps))
+    }
+
+    /// Build the graph of the source files of `project`, parsed on `jobs` threads.
+    ///
+    /// Compilation units whose name cannot be computed, e.g. because of syntax errors, are
+    /// skipped.
+    pub fn from_project(project: GprProject, jobs: NonZeroUsize) -> Result<Self> {
+        let analyses = analyze_project(project, jobs, |unit| {
+            let filename = unit.filename();
+            unit.compilation_units()
+                .iter()
+                .filter_map(|cu| UnitDependencies::from_compilation_unit(cu, &filename).ok())
+                .collect::<Vec<_>>()
+        })?;
+
+        Ok(Self::new(
+            analyses
+                .into_iter()
+                .filter_map(|analysis| analysis.result.ok())
+                .flatten(),
+        ))
+    }
+
+    /// Iterate over the units of the graph, sorted by id.
+    pub fn units(&self) -> impl Iterator<Item = &UnitId> {
+        self.units.iter().map(|unit| &unit.id)
+    }
+
+    /// Whether `id` is in the graph.
+    pub fn contains(&self, id: &UnitId) -> bool {
+        self.index.contains_key(id)
+    }
+
+    /// Return the source file of a unit, or `None` for external or unknown units.
+    pub fn filename(&self, id: &UnitId) -> Option<&str> {
+        self.unit(id)?.filename.as_deref()
+    }
+
+    /// Whether the unit was not added to the graph, but only depended upon.
+    pub fn is_external(&self, id: &UnitId) -> bool {
+        self.unit(id).is_some_and(|unit| unit.filename.is_none())
+    }
+
+    fn unit(&self, id: &UnitId) -> Option<&GraphUnit> {
+        self.index.get(id).map(|&i| &self.units[i])
+    }
+
+    /// Return the direct dependencies of a unit.
+    pub fn dependencies(&self, id: &UnitId) -> Vec<(&UnitId, DependencyKind)> {
+        self.unit(id)
+            .map(|unit| self.resolve(&unit.dependencies))
+            .unwrap_or_default()
+    }
+
+    /// Return the units that directly depend on a unit.
+    pub fn dependents(&self, id: &UnitId) -> Vec<(&UnitId, DependencyKind)> {
+        self.unit(id)
+            .map(|unit| self.resolve(&unit.dependents))
+            .unwrap_or_default()
+    }
+
+    fn resolve(&self, edges: &[(usize, DependencyKind)]) -> Vec<(&UnitId, DependencyKind)> {
+        edges
+            .iter()
+            .map(|&(i, kind)| (&self.units[i].id, kind))
+            .collect()
+    }
+
+    /// Return all the units that transitively depend on a unit, i.e. that are affected by a
+    /// change in it, sorted by id. The unit itself is not included.
+    pub fn affected_by(&self, id: &UnitId) -> Vec<&UnitId> {
+        let Some(&start) = self.index.get(id) else {
+            return Vec::new();
+        };
+
+        let mut seen = vec![false; self.units.len()];
+        let mut stack = vec![start];
+        seen[start] = true;
+        while let Some(i) = stack.pop() {
+            for &(dependent, _) in &self.units[i].dependents {
+                if !seen[dependent] {
+                    seen[dependent] = true;
+                    stack.push(dependent);
+                }
+            }
+        }
+        seen[start] = false;
+
+        (0..self.units.len())
+            .filter(|&i| seen[i])
+            .map(|i| &self.units[i].id)
+            .collect()
+    }
+
+    /// Return the units ordered so that each unit comes after its dependencies, or the cycles
+    /// that prevent it.
+    ///
+    /// Limited `with` clauses are ignored.
+    pub fn topological_order(&self) -> Result<Vec<&UnitId>, Vec<Vec<&UnitId>>> {
+        let components = self.strongly_connected_components();
+        if components.iter().any(|c| self.is_cycle(c)) {
+            return Err(self.cycles());
+        }
+        Ok(components
+            .into_iter()
+            .flatten()
+            .map(|i| &self.units[i].id)
+            .collect())
+    }
+
+    /// Return the dependency cycles of the graph. Each cycle is a set of units, sorted by id,
+    /// that all depend on each other.
+    ///
+    /// Limited `with` clauses are ignored.
+    pub fn cycles(&self) -> Vec<Vec<&UnitId>> {
+        let mut res = self
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|c| self.is_cycle(c))
+            .map(|mut c| {
+                c.sort();
+                c.into_iter().map(|i| &self.units[i].id).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        res.sort();
+        res
+    }
+
+    /// Dependencies considered for the elaboration order.
+    fn ordering_edges(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.units[i]
+            .dependencies
+            .iter()
+            .filter(|(_, kind)| *kind != DependencyKind::LimitedWith)
+            .map(|&(to, _)| to)
+    }
+
+    fn is_cycle(&self, component: &[usize]) -> bool {
+        match component {
+            [i] => self.ordering_edges(*i).any(|to| to == *i),
+            _ => true,
+        }
+    }
+
+    /// Tarjan's algorithm, without recursion. Components are returned in topological order, i.e.
+    /// dependencies first.
+    fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        const UNVISITED: usize = usize::MAX;
+
+        let count = self.units.len();
+        let edges = (0..count)
+            .map(|i| self.ordering_edges(i).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut index = vec![UNVISITED; count];
+        let mut low = vec![0; count];
+        let mut on_stack = vec![false; count];
+        let mut stack = Vec::new();
+        let mut next = 0;
+        let mut res = Vec::new();
+
+        for root in 0..count {
+            if index[root] != UNVISITED {
+                continue;
+            }
+
+            // (unit, number of its successors visited so far)
+            let mut calls = vec![(root, 0)];
+            index[root] = next;
+            low[root] = next;
+            next += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some(&mut (v, ref mut visited)) = calls.last_mut() {
+                if let Some(&w) = edges[v].get(*visited) {
+                    *visited += 1;
+                    if index[w] == UNVISITED {
+                        index[w] = next;
+                        low[w] = next;
+                        next += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        calls.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = low[v].min(index[w]);
+                    }
+                    continue;
+                }
+
+                calls.pop();
+                if let Some(&(u, _)) = calls.last() {
+                    low[u] = low[u].min(low[v]);
+                }
+                if low[v] == index[v] {
+                    let mut component = Vec::new();
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    res.push(component);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Export the graph in the DOT format of Graphviz.
+    ///
+    /// External units are dashed, and so are limited `with` dependencies.
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph dependencies {\n");
+        for unit in &self.units {
+            let style = if unit.filename.is_none() {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            res.push_str(&format!("    \"{}\" [shape=box{style}];\n", unit.id));
+        }
+        for unit in &self.units {
+            for &(to, kind) in &unit.dependencies {
+                let style = if kind == DependencyKind::LimitedWith {
+                    ", style=dashed"
+                } else {
+                    ""
+                };
+                res.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"{style}];\n",
+                    unit.id,
+                    self.units[to].id,
+                    kind.name()
+                ));
+            }
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Serialize the graph to a JSON string, with the list of units and the list of
+    /// dependencies.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("dependency graphs are always serializable")
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use serde::Serialize;
+
+    use super::{DependencyGraph, DependencyKind, UnitId};
+
+    #[derive(Serialize)]
+    struct Graph<'a> {
+        units: Vec<Unit<'a>>,
+        dependencies: Vec<Dependency<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Unit<'a> {
+        #[serde(flatten)]
+        id: &'a UnitId,
+        filename: Option<&'a str>,
+    }
+
+    #[derive(Serialize)]
+    struct Dependency<'a> {
+        from: &'a UnitId,
+        to: &'a UnitId,
+        kind: DependencyKind,
+    }
+
+    impl Serialize for DependencyGraph {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Graph {
+                units: self
+                    .units
+                    .iter()
+                    .map(|unit| Unit {
+                        id: &unit.id,
+                        filename: unit.filename.as_deref(),
+                    })
+                    .collect(),
+                dependencies: self
+                    .units
+                    .iter()
+                    .flat_map(|unit| {
+                        unit.dependencies.iter().map(|&(to, kind)| Dependency {
+                            from: &unit.id,
+                            to: &self.units[to].id,
+                            kind,
+                        })
+                    })
+                    .collect(),
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(id: UnitId, withs: &[&str]) -> UnitDependencies {
+        UnitDependencies {
+            filename: format!(
+                "{}.ad{}",
+                id.name,
+                if id.kind == UnitKind::Spec { 's' } else { 'b' }
+            ),
+            id,
+            withed_units: withs
+                .iter()
+                .map(|name| WithedUnit {
+                    name: name.to_string(),
+                    is_limited: false,
+                    is_private: false,
+                })
+                .collect(),
+            separate_parent: None,
+            resolved_withs: Vec::new(),
+            spec: None,
+        }
+    }
+
+    fn spec(name: &str) -> UnitId {
+        UnitId::spec(name)
+    }
+
+    fn body(name: &str) -> UnitId {
+        UnitId::body(name)
+    }
+
+    fn position(order: &[&UnitId], id: &UnitId) -> usize {
+        order.iter().position(|unit| *unit == id).unwrap()
+    }
+
+    #[test]
+    fn topological_order() {
+        let graph = DependencyGraph::new([
+            unit(body("main"), &["a"]),
+            unit(spec("a"), &["b"]),
+            unit(body("a"), &["c"]),
+            unit(spec("b"), &[]),
+            unit(spec("c"), &["b"]),
+            unit(spec("a.child"), &[]),
+        ]);
+        let order = graph.topological_order().unwrap();
+        assert_eq!(order.len(), 6);
+        for unit in graph.units() {
+            for (dependency, _) in graph.dependencies(unit) {
+                assert!(position(&order, dependency) < position(&order, unit));
+            }
+        }
+        assert_eq!(
+            graph.dependencies(&spec("a.child")),
+            [(&spec("a"), DependencyKind::Parent)]
+        );
+        assert_eq!(
+            graph.affected_by(&spec("b")),
+            [
+                &spec("a"),
+                &body("a"),
+                &spec("a.child"),
+                &spec("c"),
+                &body("main")
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let graph = DependencyGraph::new([
+            unit(spec("a"), &["b"]),
+            unit(spec("b"), &["c"]),
+            unit(spec("c"), &["a"]),
+            unit(spec("d"), &["d"]),
+            unit(spec("e"), &["a"]),
+        ]);
+        let (a, b, c, d) = (spec("a"), spec("b"), spec("c"), spec("d"));
+        let cycles = vec![vec![&a, &b, &c], vec![&d]];
+        assert_eq!(graph.cycles(), cycles);
+        assert_eq!(graph.topological_order(), Err(cycles));
+    }
+
+    #[test]
+    fn strongly_connected_components() {
+        let graph = DependencyGraph::new([
+            unit(spec("a"), &["b"]),
+            unit(spec("b"), &["a", "c"]),
+            unit(spec("c"), &[]),
+            unit(spec("d"), &["b"]),
+        ]);
+        let components = graph
+            .strongly_connected_components()
+            .into_iter()
+            .map(|mut component| {
+                component.sort();
+                component
+                    .into_iter()
+                    .map(|i| graph.units[i].id.name.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(components, [vec!["c"], vec!["a", "b"], vec!["d"]]);
+    }
+
+    #[test]
+    fn limited_with() {
+        let mut a = unit(spec("a"), &["b"]);
+        a.withed_units[0].is_limited = true;
+        let graph = DependencyGraph::new([a, unit(spec("b"), &["a"])]);
+        assert!(graph.cycles().is_empty());
+        assert_eq!(graph.topological_order().unwrap(), [&spec("a"), &spec("b")]);
+    }
+
+    #[test]
+    fn subprogram_bodies() {
+        // `proc` has no specification, and `ext` is not in the graph but was resolved
+        let mut main = unit(body("main"), &["proc", "ext"]);
+        main.resolved_withs = vec![body("ext")];
+        let graph = DependencyGraph::new([main, unit(body("proc"), &[])]);
+        assert_eq!(
+            graph.dependencies(&body("main")),
+            [
+                (&body("ext"), DependencyKind::With),
+                (&body("proc"), DependencyKind::With)
+            ]
+        );
+        assert!(!graph.contains(&spec("proc")));
+        assert!(graph.is_external(&body("ext")));
+    }
+
+    #[test]
+    fn bodies_and_subunits() {
+        let mut sub = unit(body("p.q"), &[]);
+        sub.separate_parent = Some("p".into());
+        let mut p_body = unit(body("p"), &[]);
+        p_body.spec = Some(spec("p"));
+        let graph = DependencyGraph::new([sub, p_body]);
+        assert_eq!(
+            graph.dependencies(&body("p.q")),
+            [(&body("p"), DependencyKind::Separate)]
+        );
+        assert_eq!(
+            graph.dependencies(&body("p")),
+            [(&spec("p"), DependencyKind::Body)]
+        );
+        assert!(graph.is_external(&spec("p")));
+    }
+}
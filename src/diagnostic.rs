@@ -1,24 +1,183 @@
-use libadalang_sys::ada_diagnostic;
+//! Source locations and diagnostics
+
+use std::fmt::Display;
+
+use libadalang_sys::{ada_diagnostic, ada_source_location, ada_source_location_range};
 
 use crate::text::Text;
 
-pub use libadalang_sys::{
-    ada_source_location as SourceLocation, ada_source_location_range as SourceLocationRange,
-};
+mod render;
+
+pub use render::Renderer;
+pub(crate) use render::unit_source;
+
+/// A location in a source file. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLocation {
+    pub line: u32,
+    pub column: u16,
+}
+
+impl SourceLocation {
+    pub fn new(line: u32, column: u16) -> Self {
+        Self { line, column }
+    }
+
+    pub fn from_raw(raw: ada_source_location) -> Self {
+        Self {
+            line: raw.line,
+            column: raw.column,
+        }
+    }
+
+    pub fn to_raw(self) -> ada_source_location {
+        ada_source_location {
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A range of source locations, from `start` included to `end` excluded.
+///
+/// Ranges are ordered by start, then by end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceRange {
+    pub start: SourceLocation,
+    pub end: SourceLocation,
+}
+
+impl SourceRange {
+    pub fn new(start: SourceLocation, end: SourceLocation) -> Self {
+        Self { start, end }
+    }
+
+    pub fn from_raw(raw: ada_source_location_range) -> Self {
+        Self {
+            start: SourceLocation::from_raw(raw.start),
+            end: SourceLocation::from_raw(raw.end),
+        }
+    }
+
+    pub fn to_raw(self) -> ada_source_location_range {
+        ada_source_location_range {
+            start: self.start.to_raw(),
+            end: self.end.to_raw(),
+        }
+    }
+
+    /// Whether `loc` is inside this range.
+    pub fn contains(&self, loc: SourceLocation) -> bool {
+        self.start <= loc && loc < self.end
+    }
 
+    /// Whether `other` is entirely inside this range.
+    pub fn contains_range(&self, other: &SourceRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+}
+
+/// Former name of [`SourceRange`].
+#[deprecated(note = "renamed to `SourceRange`")]
+pub type SourceLocationRange = SourceRange;
+
+impl Display for SourceRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Severity of a diagnostic.
+///
+/// Libadalang only reports errors, warnings come from user code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// What produced a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiagnosticOrigin {
+    /// The parser, for syntax errors
+    Parser,
+    /// The file reader, including the preprocessor, e.g. when the source could not be read.
+    ///
+    /// These are the diagnostics returned by a [`FileReader`](crate::file_reader::FileReader),
+    /// and those of libadalang's readers that have no location.
+    Reader,
+    /// User code
+    Other,
+}
+
+/// A diagnostic, i.e. a message attached to a range of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Diagnostic {
-    pub sloc_range: SourceLocationRange,
+    /// Name of the source file, if known
+    pub filename: Option<String>,
+    pub sloc_range: SourceRange,
     pub message: String,
+    pub severity: Severity,
+    pub origin: DiagnosticOrigin,
 }
 
 impl Diagnostic {
-    pub fn from_raw(raw: ada_diagnostic) -> Self {
+    /// Create an error diagnostic without a filename.
+    pub fn new(sloc_range: SourceRange, message: impl Into<String>) -> Self {
         Self {
-            sloc_range: raw.sloc_range,
-            message: Text::from_raw_borrow(&raw.message).to_string(),
+            filename: None,
+            sloc_range,
+            message: message.into(),
+            severity: Severity::Error,
+            origin: DiagnosticOrigin::Other,
         }
     }
 
+    /// Set the name of the source file of this diagnostic.
+    pub fn with_filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_origin(mut self, origin: DiagnosticOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Convert a raw diagnostic, reported by the parser.
+    pub fn from_raw(raw: ada_diagnostic) -> Self {
+        Self::new(
+            SourceRange::from_raw(raw.sloc_range),
+            Text::from_raw_borrow(&raw.message).to_string(),
+        )
+        .with_origin(DiagnosticOrigin::Parser)
+    }
+
     pub fn to_raw(&self) -> ada_diagnostic {
         make_diag(
             [
@@ -30,14 +189,29 @@ impl Diagnostic {
     }
 }
 
+impl Display for Diagnostic {
+    /// Format the diagnostic as `file:line:col: message`, or `line:col: message` without a
+    /// filename. Warnings are prefixed with `warning: `.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(filename) = &self.filename {
+            write!(f, "{filename}:")?;
+        }
+        write!(f, "{}: ", self.sloc_range.start)?;
+        if self.severity == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
 pub(crate) fn make_diag(sloc_range: [(u32, u16); 2], msg: &str) -> ada_diagnostic {
     ada_diagnostic {
-        sloc_range: SourceLocationRange {
-            start: SourceLocation {
+        sloc_range: ada_source_location_range {
+            start: ada_source_location {
                 line: sloc_range[0].0,
                 column: sloc_range[0].1,
             },
-            end: SourceLocation {
+            end: ada_source_location {
                 line: sloc_range[1].0,
                 column: sloc_range[1].1,
             },
@@ -0,0 +1,175 @@
+//! Rendering of diagnostics with source snippets
+//!
+//! Diagnostics are printed like compiler errors, with the source lines they cover and an
+//! underline below the offending range:
+//!
+//! ```text
+//! error: Expected ';', got String_Literal
+//!   --> main.adb:3:13
+//!   |
+//! 3 |    Put_Line "Hello"
+//!   |             ^^^^^^^
+//! ```
+
+use std::{fmt::Write, mem::MaybeUninit, num::NonZeroU8};
+
+use libadalang_sys::{ada_token, ada_token_sloc_range, ada_unit_first_token};
+
+use crate::{analysis::Unit, exception::Exception};
+
+use super::{Diagnostic, Severity, SourceRange};
+
+const RESET: &str = "\x1b[0m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+
+/// Renderer of diagnostics with source snippets, and optionally ANSI colors.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Renderer {
+    colors: bool,
+}
+
+impl Renderer {
+    /// Create a renderer without colors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to use ANSI escape codes to color the output.
+    pub fn with_colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    fn paint(&self, style: &'static str, text: &str) -> String {
+        if self.colors {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Expand the tabs of `line` into spaces, so that character indices are columns.
+    fn expand_tabs(line: &str, tab_stop: NonZeroU8) -> Vec<char> {
+        let tab_stop = usize::from(tab_stop.get());
+        let mut res = Vec::with_capacity(line.len());
+        for c in line.chars() {
+            if c == '\t' {
+                let width = tab_stop - res.len() % tab_stop;
+                res.extend(core::iter::repeat_n(' ', width));
+            } else {
+                res.push(c);
+            }
+        }
+        res
+    }
+
+    /// Render `diag` with the lines of `source` it covers.
+    ///
+    /// `tab_stop` must be the tab stop of the context that produced the diagnostic (see
+    /// [`Context::tab_stop`](crate::analysis::Context::tab_stop)), so that underlines match its
+    /// columns.
+    pub fn render(&self, source: &str, tab_stop: NonZeroU8, diag: &Diagnostic) -> String {
+        let SourceRange { start, end } = diag.sloc_range;
+        let first = start.line.max(1);
+        let last = end.line.max(first);
+        let width = last.to_string().len();
+        let gutter = self.paint(BOLD_BLUE, &format!("{:width$} |", ""));
+
+        let (color, label) = match diag.severity {
+            Severity::Error => (BOLD_RED, "error"),
+            Severity::Warning => (BOLD_YELLOW, "warning"),
+        };
+
+        let mut res = String::new();
+        let _ = writeln!(
+            res,
+            "{}{}",
+            self.paint(color, label),
+            self.paint(BOLD, &format!(": {}", diag.message))
+        );
+        let _ = writeln!(
+            res,
+            "{}{}:{start}",
+            self.paint(BOLD_BLUE, &format!("{:width$} --> ", "")),
+            diag.filename.as_deref().unwrap_or("<unknown>"),
+        );
+        let _ = writeln!(res, "{gutter}");
+
+        let lines = source.lines().skip(first as usize - 1);
+        for (line_no, line) in (first..=last).zip(lines) {
+            let line = Self::expand_tabs(line, tab_stop);
+
+            // columns are 1-based, and the end column is excluded
+            let from = if line_no == first {
+                usize::from(start.column.max(1)) - 1
+            } else {
+                line.iter().take_while(|c| c.is_whitespace()).count()
+            };
+            let to = if line_no == last {
+                usize::from(end.column.max(1)) - 1
+            } else {
+                line.len()
+            };
+            // always show at least one caret, e.g. for an empty range or at the end of a line
+            let to = to.max(from + usize::from(line_no == first));
+
+            let _ = writeln!(
+                res,
+                "{} {}",
+                self.paint(BOLD_BLUE, &format!("{line_no:width$} |")),
+                line.iter().collect::<String>()
+            );
+            if to > from {
+                let _ = writeln!(
+                    res,
+                    "{gutter} {}{}",
+                    " ".repeat(from),
+                    self.paint(color, &"^".repeat(to - from))
+                );
+            }
+        }
+
+        res
+    }
+
+    /// Render all the diagnostics of `unit`, separated by empty lines, with the tab stop of its
+    /// context.
+    pub fn render_unit(&self, unit: &Unit) -> String {
+        let source = unit_source(unit);
+        let tab_stop = unit.tab_stop();
+        unit.diagnostics()
+            .map(|diag| self.render(&source, tab_stop, &diag))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Return the source text of `unit`, with lines and columns matching its source locations.
+///
+/// The text before the first token is replaced by blank space, since libadalang only returns
+/// the text from the first token. Units without tokens, e.g. that could not be read, have an
+/// empty source, so their diagnostics are rendered without snippets.
+pub(crate) fn unit_source(unit: &Unit) -> String {
+    let Ok(text) = unit.unparse() else {
+        return String::new();
+    };
+
+    let mut first = MaybeUninit::<ada_token>::uninit();
+    let mut sloc_range = MaybeUninit::uninit();
+    let start = unsafe {
+        ada_unit_first_token(unit.as_raw(), first.as_mut_ptr());
+        ada_token_sloc_range(first.as_mut_ptr(), sloc_range.as_mut_ptr());
+        SourceRange::from_raw(sloc_range.assume_init()).start
+    };
+    if Exception::wrap(()).is_err() {
+        return String::new();
+    }
+
+    let mut res = "\n".repeat(start.line.saturating_sub(1) as usize);
+    res.push_str(&" ".repeat(usize::from(start.column.saturating_sub(1))));
+    res.push_str(&text);
+    res
+}
@@ -0,0 +1,164 @@
+//! Parallel analysis of all the source files of a project
+//!
+//! [`analyze_project`] parses every source file of a GPR project on a [`ContextPool`], and calls
+//! a function on each unit. Diagnostics are collected along with the results:
+//!
+//! ```no_run
+//! # use std::num::NonZeroUsize;
+//! # use libadalang::{driver::analyze_project, gpr_project::GprProject};
+//! # fn f() -> libadalang::Result<()> {
+//! let project = GprProject::build("main.gpr").load()?;
+//! let jobs = NonZeroUsize::new(8).unwrap();
+//! for file in analyze_project(project, jobs, |unit| unit.token_count())? {
+//!     println!("{}: {} tokens", file.filename, file.result?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    num::{NonZeroU8, NonZeroUsize},
+    thread,
+};
+
+use crate::{
+    Result,
+    analysis::{
+        ContextPool, Unit,
+        context::{ContextBuilder, DEFAULT_TAB_STOP},
+    },
+    diagnostic::Diagnostic,
+    gpr_project::{GprProject, SourceFilesMode},
+};
+
+/// Result of the analysis of a source file.
+#[derive(Debug)]
+pub struct FileAnalysis<R> {
+    pub filename: String,
+    /// Diagnostics of the unit, empty if it could not be parsed
+    pub diagnostics: Vec<Diagnostic>,
+    /// Result of the analysis function, or the error raised when getting the unit
+    pub result: Result<R>,
+}
+
+/// Progress of a project analysis, reported after each file.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// Number of files analyzed so far
+    pub done: usize,
+    pub total: usize,
+    /// The file that was just analyzed
+    pub filename: &'a str,
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(Progress<'_>) + 'a>;
+
+/// Analysis of the source files of a project, on several threads.
+pub struct ProjectAnalyzer<'a> {
+    project: GprProject,
+    jobs: NonZeroUsize,
+    mode: SourceFilesMode,
+    tab_stop: NonZeroU8,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a> ProjectAnalyzer<'a> {
+    /// Create an analyzer for the default source files of `project`, with one job per available
+    /// core.
+    pub fn new(project: GprProject) -> Self {
+        Self {
+            project,
+            jobs: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            mode: SourceFilesMode::Default,
+            tab_stop: DEFAULT_TAB_STOP,
+            progress: None,
+        }
+    }
+
+    /// Set the number of worker threads, each with its own context.
+    pub fn jobs(mut self, jobs: NonZeroUsize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Set which source files of the project to analyze.
+    pub fn mode(mut self, mode: SourceFilesMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the tab stop of the contexts, see [`ContextBuilder::tab_stop`]. This changes the
+    /// columns of the source locations found by the analysis.
+    pub fn tab_stop(mut self, tab_stop: NonZeroU8) -> Self {
+        self.tab_stop = tab_stop;
+        self
+    }
+
+    /// Call `progress` after each analyzed file, on the calling thread.
+    pub fn on_progress(mut self, progress: impl FnMut(Progress<'_>) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Parse every source file, and call `f` on its unit in the context of a worker.
+    ///
+    /// Returns the analyses in the order of the source files of the project. Fails only if the
+    /// source files cannot be listed, or the contexts cannot be created. If `f` panics, the panic
+    /// is resumed on the calling thread once all files are done.
+    pub fn run<F, R>(mut self, f: F) -> Result<Vec<FileAnalysis<R>>>
+    where
+        F: Fn(Unit) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let files = self.project.source_files(self.mode)?;
+        let builder = ContextBuilder::new(self.project).tab_stop(self.tab_stop);
+        let pool = ContextPool::new(builder, self.jobs)?;
+
+        let mut done = 0;
+        let results = pool.map_files(
+            files.iter().cloned(),
+            move |unit| (unit.diagnostics().collect::<Vec<_>>(), f(unit)),
+            |idx| {
+                done += 1;
+                if let Some(progress) = &mut self.progress {
+                    progress(Progress {
+                        done,
+                        total: files.len(),
+                        filename: &files[idx],
+                    });
+                }
+            },
+        );
+
+        Ok(files
+            .iter()
+            .zip(results)
+            .map(|(filename, res)| {
+                let (diagnostics, result) = match res {
+                    Ok((diagnostics, result)) => (diagnostics, Ok(result)),
+                    Err(err) => (Vec::new(), Err(err)),
+                };
+                FileAnalysis {
+                    filename: filename.clone(),
+                    diagnostics,
+                    result,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Analyze the default source files of `project` on `jobs` threads, calling `f` on each unit.
+///
+/// See [`ProjectAnalyzer`] to report progress or select other source files.
+pub fn analyze_project<F, R>(
+    project: GprProject,
+    jobs: NonZeroUsize,
+    f: F,
+) -> Result<Vec<FileAnalysis<R>>>
+where
+    F: Fn(Unit) -> R + Send + Sync + 'static,
+    R: Send + 'static,
+{
+    ProjectAnalyzer::new(project).jobs(jobs).run(f)
+}
@@ -1,25 +1,31 @@
 use std::ffi::c_void;
 
 use libadalang_sys::{
-    ada_analysis_context, ada_analysis_unit, ada_bool, ada_create_event_handler,
-    ada_dec_ref_event_handler, ada_event_handler, ada_text,
+    ada_analysis_context, ada_analysis_unit, ada_bool, ada_context_incref,
+    ada_create_event_handler, ada_dec_ref_event_handler, ada_event_handler, ada_text,
 };
 
 use crate::{
+    Result,
     analysis::{Context, unit::Unit},
-    exception::Exception,
+    exception::{self, Exception},
     text::Text,
 };
 
-pub trait EventHandlerInterface: Sized + 'static {
+pub trait EventHandlerInterface: Sized + Send + 'static {
     fn unit_requested(&mut self, ctx: Option<Context>, event: UnitRequestedEvent);
     fn unit_parsed(&mut self, ctx: Option<Context>, event: UnitParsedEvent);
 
-    fn as_event_handler(self) -> Result<EventHandler, Exception> {
+    fn as_event_handler(self) -> Result<EventHandler> {
         EventHandler::new(self, Self::unit_requested, Self::unit_parsed)
     }
 }
 
+/// An event handler, to pass to a context.
+///
+/// Its callbacks run on the thread of the context, so they must be `Send`. If a callback panics,
+/// the panic is stored in the context, and resumed by the next call to the context, to one of its
+/// units or to one of their nodes, e.g. when the function that triggered the event returns.
 pub struct EventHandler(ada_event_handler);
 
 struct EventHandlerData<D, F, G> {
@@ -32,7 +38,19 @@ unsafe extern "C-unwind" fn destroy_cb<D, F, G>(data: *mut c_void) {
     let ptr = data.cast::<EventHandlerData<D, F, G>>();
 
     let boxed: Box<EventHandlerData<D, F, G>> = unsafe { Box::from_raw(ptr) };
-    drop(boxed);
+    let _ = exception::catch_callback_panic(|| drop(boxed));
+}
+
+/// Create a handle to the context of a callback. The callback only borrows the context, so the
+/// handle takes a new reference, released when the handle is dropped.
+///
+/// # Safety
+/// The `context` value must be a valid analysis context, or a null pointer.
+unsafe fn borrowed_context(context: ada_analysis_context) -> Option<Context> {
+    if context.is_null() {
+        return None;
+    }
+    unsafe { Context::from_raw(ada_context_incref(context)) }
 }
 
 unsafe extern "C-unwind" fn unit_requested_cb<D, F, G>(
@@ -43,7 +61,7 @@ unsafe extern "C-unwind" fn unit_requested_cb<D, F, G>(
     found: ada_bool,
     is_not_found_error: ada_bool,
 ) where
-    F: FnMut(&mut D, Option<Context>, UnitRequestedEvent) + 'static,
+    F: FnMut(&mut D, Option<Context>, UnitRequestedEvent) + Send + 'static,
 {
     let name = if name.is_null() {
         String::new()
@@ -65,9 +83,11 @@ unsafe extern "C-unwind" fn unit_requested_cb<D, F, G>(
     let data = data.cast::<EventHandlerData<D, F, G>>();
     let data: &mut EventHandlerData<D, F, G> = unsafe { &mut *data };
 
-    let ctx = unsafe { Context::from_raw(context) };
+    let ctx = unsafe { borrowed_context(context) };
 
-    (data.unit_requested_cb)(&mut data.data, ctx, event);
+    let _ = exception::catch_context_callback_panic(context, || {
+        (data.unit_requested_cb)(&mut data.data, ctx, event);
+    });
 }
 
 unsafe extern "C-unwind" fn unit_parsed_cb<D, F, G>(
@@ -76,7 +96,7 @@ unsafe extern "C-unwind" fn unit_parsed_cb<D, F, G>(
     unit: ada_analysis_unit,
     reparsed: ada_bool,
 ) where
-    G: FnMut(&mut D, Option<Context>, UnitParsedEvent) + 'static,
+    G: FnMut(&mut D, Option<Context>, UnitParsedEvent) + Send + 'static,
 {
     let unit = unsafe { Unit::from_raw(unit) };
     let reparsed = reparsed != 0;
@@ -86,9 +106,11 @@ unsafe extern "C-unwind" fn unit_parsed_cb<D, F, G>(
     let data = data.cast::<EventHandlerData<D, F, G>>();
     let data: &mut EventHandlerData<D, F, G> = unsafe { &mut *data };
 
-    let ctx = unsafe { Context::from_raw(context) };
+    let ctx = unsafe { borrowed_context(context) };
 
-    (data.unit_parsed_cb)(&mut data.data, ctx, event);
+    let _ = exception::catch_context_callback_panic(context, || {
+        (data.unit_parsed_cb)(&mut data.data, ctx, event);
+    });
 }
 
 pub struct UnitRequestedEvent {
@@ -104,11 +126,11 @@ pub struct UnitParsedEvent {
 }
 
 impl EventHandler {
-    fn new<D, F, G>(data: D, unit_requested: F, unit_parsed: G) -> Result<Self, Exception>
+    fn new<D, F, G>(data: D, unit_requested: F, unit_parsed: G) -> Result<Self>
     where
-        D: 'static,
-        F: FnMut(&mut D, Option<Context>, UnitRequestedEvent) + 'static,
-        G: FnMut(&mut D, Option<Context>, UnitParsedEvent) + 'static,
+        D: Send + 'static,
+        F: FnMut(&mut D, Option<Context>, UnitRequestedEvent) + Send + 'static,
+        G: FnMut(&mut D, Option<Context>, UnitParsedEvent) + Send + 'static,
     {
         let boxed = Box::new(EventHandlerData {
             data,
@@ -125,10 +147,18 @@ impl EventHandler {
             )
         };
 
-        Exception::wrap(EventHandler(evh))
+        Ok(Exception::wrap(EventHandler(evh))?)
+    }
+
+    /// Get the inner raw event handler.
+    pub fn as_raw(&self) -> ada_event_handler {
+        self.0
     }
 }
 
+// SAFETY: the handler holds a single reference, and its callbacks and data are `Send`
+unsafe impl Send for EventHandler {}
+
 impl Drop for EventHandler {
     fn drop(&mut self) {
         unsafe { ada_dec_ref_event_handler(self.0) };
@@ -1,9 +1,85 @@
-use std::{error::Error, ffi::CStr, fmt::Display, process::abort};
+use std::{
+    any::Any,
+    cell::RefCell,
+    error::Error,
+    ffi::CStr,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    process::abort,
+};
 
-use libadalang_sys::{ada_exception_name, ada_get_last_exception};
+use libadalang_sys::{ada_analysis_context, ada_exception_name, ada_get_last_exception};
+
+use crate::analysis::context;
 
 pub type ExceptionKind = libadalang_sys::ada_exception_kind;
 
+thread_local! {
+    /// Panic caught in a callback called by libadalang outside of a context, e.g. in a file
+    /// reader, to resume once back in Rust code.
+    static CALLBACK_PANIC: RefCell<Option<Box<dyn Any + Send>>> = const { RefCell::new(None) };
+}
+
+/// Run the body of a callback called by libadalang, catching panics: unwinding through Ada
+/// frames would corrupt the state of the library.
+///
+/// The panic is stored, and resumed by the next [`Exception::wrap`] on this thread, i.e. after
+/// the libadalang function that called the callback returns. Returns the panic message if `f`
+/// panicked.
+pub(crate) fn catch_callback_panic<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    catch(f, store_callback_panic)
+}
+
+/// Like [`catch_callback_panic`], for a callback of the context `context`, such as an event
+/// handler: the panic is stored in the context, and resumed by the next call to the context, to
+/// one of its units or to one of their nodes.
+pub(crate) fn catch_context_callback_panic<R>(
+    context: ada_analysis_context,
+    f: impl FnOnce() -> R,
+) -> Result<R, String> {
+    catch(f, |payload| match context::data_of(context) {
+        Some(data) => data.store_callback_panic(payload),
+        None => store_callback_panic(payload),
+    })
+}
+
+fn catch<R>(f: impl FnOnce() -> R, store: impl FnOnce(Box<dyn Any + Send>)) -> Result<R, String> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => Ok(res),
+        Err(payload) => {
+            let msg = panic_message(&*payload).to_owned();
+            store(payload);
+            Err(msg)
+        }
+    }
+}
+
+/// Store the panic of a callback on this thread, to resume it with [`resume_callback_panic`].
+pub(crate) fn store_callback_panic(payload: Box<dyn Any + Send>) {
+    CALLBACK_PANIC.with_borrow_mut(|pending| {
+        // keep the first panic, the next ones are likely consequences of it
+        pending.get_or_insert(payload);
+    });
+}
+
+/// Return the message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// Resume the panic caught in a callback on this thread, if any.
+pub fn resume_callback_panic() {
+    if let Some(payload) = CALLBACK_PANIC.take() {
+        panic::resume_unwind(payload);
+    }
+}
+
 #[derive(Debug)]
 pub struct Exception {
     kind: ExceptionKind,
@@ -11,6 +87,14 @@ pub struct Exception {
 }
 
 impl Exception {
+    /// Create an exception, to report an error that the Rust side detected before libadalang.
+    pub(crate) fn new(kind: ExceptionKind, msg: &str) -> Self {
+        Self {
+            kind,
+            msg: msg.into(),
+        }
+    }
+
     /// Get the last raised exception
     pub fn get_last() -> Option<Self> {
         let ptr = unsafe { ada_get_last_exception() };
@@ -30,7 +114,12 @@ impl Exception {
 
     /// Return `Err(...)` if the last operation raised an exception, or `Ok(val)` with the passed
     /// value.
+    ///
+    /// # Panics
+    /// Resumes the panic of a callback called during the last operation, see
+    /// [`resume_callback_panic`].
     pub fn wrap<T>(val: T) -> Result<T, Self> {
+        resume_callback_panic();
         match Self::get_last() {
             None => Ok(val),
             Some(e) => Err(e),
@@ -1,4 +1,6 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     ffi::{CStr, c_char, c_int, c_void},
     mem::ManuallyDrop,
     ptr::NonNull,
@@ -10,11 +12,46 @@ use libadalang_sys::{
 };
 
 use crate::{
-    diagnostic::{self, Diagnostic},
-    exception::Exception,
+    Result,
+    diagnostic::{self, Diagnostic, SourceRange},
+    exception::{self, Exception},
     text::Text,
 };
 
+thread_local! {
+    /// Diagnostic returned by the last read of each file on this thread, if the read failed, to
+    /// tell them apart from parsing diagnostics. Contexts are not `Send`, so their file readers
+    /// run on their thread.
+    static READ_DIAGNOSTICS: RefCell<HashMap<String, (SourceRange, String)>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Record the outcome of reading `filename`.
+fn record_read(filename: &str, diag: Option<&ada_diagnostic>) {
+    READ_DIAGNOSTICS.with_borrow_mut(|diags| match diag {
+        Some(diag) => {
+            let message = Text::from_raw_borrow(&diag.message).to_string();
+            diags.insert(
+                filename.to_owned(),
+                (SourceRange::from_raw(diag.sloc_range), message),
+            );
+        }
+        None => {
+            diags.remove(filename);
+        }
+    });
+}
+
+/// Whether `diag`, reported for the unit of `filename`, was returned by a file reader when it
+/// last read this file.
+pub(crate) fn is_read_diagnostic(filename: &str, diag: &Diagnostic) -> bool {
+    READ_DIAGNOSTICS.with_borrow(|diags| {
+        diags.get(filename).is_some_and(|(sloc_range, message)| {
+            *sloc_range == diag.sloc_range && *message == diag.message
+        })
+    })
+}
+
 /// A file reader wrapping a custom callback
 pub struct FileReader(
     // use a non-null pointer for niche optimisations :)
@@ -33,7 +70,7 @@ pub struct FileRequest<'a> {
 unsafe extern "C-unwind" fn destroy_callback<F>(data: *mut c_void) {
     let ptr = data as *mut F;
     let boxed: Box<F> = unsafe { Box::from_raw(ptr) };
-    drop(boxed)
+    let _ = exception::catch_callback_panic(|| drop(boxed));
 }
 
 /// SAFETY: the `data` pointer must point to a valid `F`.
@@ -50,7 +87,7 @@ unsafe extern "C-unwind" fn read_callback<F>(
     buffer: *mut ada_text,
     diagnostic: *mut ada_diagnostic,
 ) where
-    for<'a> F: FnMut(FileRequest<'a>) -> Result<String, Diagnostic> + 'static,
+    for<'a> F: FnMut(FileRequest<'a>) -> Result<String, Diagnostic> + Send + 'static,
 {
     // returns static CStr, but the string may not be static!
     // do not use outside of this function.
@@ -79,18 +116,29 @@ unsafe extern "C-unwind" fn read_callback<F>(
         return;
     };
 
-    match read_cb(FileRequest {
-        filename,
-        charset,
-        read_bom: read_bom != 0,
-    }) {
-        Ok(string) => {
+    let res = exception::catch_callback_panic(|| {
+        read_cb(FileRequest {
+            filename,
+            charset,
+            read_bom: read_bom != 0,
+        })
+    });
+    let diag = match res {
+        Ok(Ok(string)) => {
             let text = Text::new(&string);
             unsafe { core::ptr::write(buffer, text.into_raw()) };
+            None
         }
-        Err(diag) => {
-            unsafe { core::ptr::write(diagnostic, diag.to_raw()) };
+        Ok(Err(diag)) => Some(diag.to_raw()),
+        Err(msg) => {
+            // the panic is resumed once the parsing function returns
+            let msg = format!("file reader panicked: {msg}");
+            Some(diagnostic::make_diag([(0, 0), (0, 0)], &msg))
         }
+    };
+    record_read(filename, diag.as_ref());
+    if let Some(diag) = diag {
+        unsafe { core::ptr::write(diagnostic, diag) };
     }
 }
 
@@ -98,9 +146,14 @@ impl FileReader {
     /// Create a new file reader with a custom static fallback.
     ///
     /// To execute custom code on drop, capture a type with custom drop glue in the closure.
-    pub fn new<F>(cb: F) -> Result<Self, Exception>
+    ///
+    /// The callback runs on the thread of the context using the reader, so it must be `Send`.
+    ///
+    /// If the callback panics, the file is reported as unreadable, and the panic is resumed when
+    /// the libadalang function that read the file returns.
+    pub fn new<F>(cb: F) -> Result<Self>
     where
-        for<'a> F: FnMut(FileRequest<'a>) -> Result<String, Diagnostic> + 'static,
+        for<'a> F: FnMut(FileRequest<'a>) -> Result<String, Diagnostic> + Send + 'static,
     {
         let boxed = Box::new(cb);
         let ptr = Box::into_raw(boxed);
@@ -122,6 +175,9 @@ impl FileReader {
     }
 }
 
+// SAFETY: the reader holds a single reference, and its callback is `Send`
+unsafe impl Send for FileReader {}
+
 impl Drop for FileReader {
     fn drop(&mut self) {
         unsafe { ada_dec_ref_file_reader(self.0.as_ptr()) };
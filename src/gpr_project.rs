@@ -1,13 +1,13 @@
 //! GPR project loading
 
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, c_char, c_int};
 
 use libadalang_sys::{
     ada_free_string_array, ada_gpr_project, ada_gpr_project_scenario_variable as ada_scenario_var,
-    ada_string_array_ptr,
+    ada_gpr_project_source_files, ada_string_array_ptr,
 };
 
-use crate::{Error, exception::Exception};
+use crate::{Error, Result, exception::Exception};
 
 /// An opaque GPR project wrapper. Can be used to build a new analysis context.
 pub struct GprProject {
@@ -17,6 +17,46 @@ pub struct GprProject {
     _builder: GprProjectBuilder,
 }
 
+/// Which source files of a project to list, see [`GprProject::source_files`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SourceFilesMode {
+    /// Sources of the root project and its dependencies, except externally built ones
+    #[default]
+    Default,
+    /// Sources of the root project only
+    RootProject,
+    /// Sources of the whole project tree, externally built projects included
+    WholeProject,
+    /// Sources of the whole project tree and of the runtime
+    WholeProjectWithRuntime,
+}
+
+/// Read the strings of a string array, and free it.
+///
+/// # Safety
+/// The `array` value must be a valid string array, or a null pointer.
+unsafe fn take_string_array(array: ada_string_array_ptr) -> Vec<String> {
+    if array.is_null() {
+        return Vec::new();
+    }
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            (*array).c_ptr,
+            usize::try_from((*array).length).unwrap_or(0),
+        )
+    };
+    let res = slice
+        .iter()
+        .map(|&ptr| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    unsafe { ada_free_string_array(array) };
+    res
+}
+
 /// Builder for the GPR project type.
 pub struct GprProjectBuilder {
     project_file: Option<CString>,
@@ -112,7 +152,7 @@ impl GprProjectBuilder {
     }
 
     /// Load the project
-    pub fn load(mut self) -> Result<GprProject, crate::Error> {
+    pub fn load(mut self) -> Result<GprProject> {
         let mut project: ada_gpr_project = core::ptr::null_mut();
         let mut errors: ada_string_array_ptr = core::ptr::null_mut();
 
@@ -156,30 +196,19 @@ impl GprProjectBuilder {
         }
 
         if let Some(err) = Exception::get_last() {
-            return Err(crate::Error::Exception(err));
+            return Err(err.into());
         }
 
         if project.is_null() || (!errors.is_null() && unsafe { (*errors).length != 0 }) {
-            let array = if errors.is_null() {
-                &[]
-            } else {
-                unsafe { core::slice::from_raw_parts((*errors).c_ptr, (*errors).length as usize) }
-            };
-
-            let errs = array
-                .iter()
-                .map(|&ptr| unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_owned())
-                .collect::<Vec<_>>()
-                .join("\n");
+            let errs = unsafe { take_string_array(errors) }.join("\n");
 
             if errs.is_empty() {
                 return Err(Error::custom("invalid project"));
             }
 
-            unsafe { ada_free_string_array(errors) };
-
             Err(errs.into())
         } else {
+            unsafe { take_string_array(errors) };
             Ok(GprProject {
                 inner: project,
                 _builder: self,
@@ -188,6 +217,9 @@ impl GprProjectBuilder {
     }
 }
 
+// SAFETY: the scenario variables are owned by the builder
+unsafe impl Send for GprProjectBuilder {}
+
 impl Drop for GprProjectBuilder {
     fn drop(&mut self) {
         for ada_scenario_var { name, value } in self.scenario_vars.drain(..) {
@@ -212,8 +244,52 @@ impl GprProject {
     pub fn as_raw(&self) -> ada_gpr_project {
         self.inner
     }
+
+    /// List the source files of the project, selected by `mode`.
+    pub fn source_files(&self, mode: SourceFilesMode) -> Result<Vec<String>> {
+        self.subproject_source_files(mode, &[])
+    }
+
+    /// List the source files of the given subprojects, selected by `mode`. With no subprojects,
+    /// this is the same as [`GprProject::source_files`].
+    pub fn subproject_source_files(
+        &self,
+        mode: SourceFilesMode,
+        subprojects: &[&str],
+    ) -> Result<Vec<String>> {
+        let subprojects = subprojects
+            .iter()
+            .map(|name| CString::new(*name).unwrap())
+            .collect::<Vec<_>>();
+        let mut ptrs = subprojects
+            .iter()
+            .map(|name| name.as_ptr())
+            .collect::<Vec<*const c_char>>();
+
+        let mut files: ada_string_array_ptr = core::ptr::null_mut();
+        unsafe {
+            ada_gpr_project_source_files(
+                self.inner,
+                mode as c_int,
+                if ptrs.is_empty() {
+                    core::ptr::null_mut()
+                } else {
+                    ptrs.as_mut_ptr()
+                },
+                ptrs.len() as c_int,
+                &raw mut files,
+            )
+        };
+        Exception::wrap(())?;
+
+        Ok(unsafe { take_string_array(files) })
+    }
 }
 
+// SAFETY: the project is owned, and freed only once. It is not `Sync`, since libadalang does not
+// support concurrent accesses to it, e.g. to initialize several contexts at once.
+unsafe impl Send for GprProject {}
+
 impl Drop for GprProject {
     fn drop(&mut self) {
         unsafe { libadalang_sys::ada_gpr_project_free(self.inner) };
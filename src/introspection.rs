@@ -0,0 +1,435 @@
+//! Introspection of node members
+//!
+//! Syntax fields and properties are listed from tables generated along with the bindings, and can
+//! be evaluated by name, e.g. to implement scripting or query languages on top of libadalang.
+
+use std::{ffi::c_void, mem::ManuallyDrop};
+
+use libadalang_sys::{
+    ada_analysis_unit, ada_base_entity, ada_big_integer, ada_bool, ada_internal_aspect,
+    ada_internal_discrete_range, ada_internal_refd_decl, ada_internal_refd_def, ada_member_param,
+    ada_node_fields, ada_node_properties, ada_node_property, ada_node_types, ada_string_dec_ref,
+    ada_string_type, ada_symbol_type, ada_text,
+};
+
+use crate::{
+    Result,
+    analysis::{Node, Unit, node::NodeKind, unit},
+    array::{LalArray, RawArray},
+    big_int::BigInt,
+    exception::Exception,
+    structs::{
+        Aspect, CompletionItem, DiscreteRange, DocAnnotation, ParamActual, ParamMatch, RefResult,
+        RefdDecl, RefdDef, Shape, Substitution,
+    },
+    symbol::Symbol,
+    text,
+};
+
+/// A parameter of a property.
+pub type MemberParam = ada_member_param;
+
+/// Whether a member is a syntax field or a property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    Field,
+    Property,
+}
+
+/// Description of a member of a node type.
+#[derive(Debug, Clone, Copy)]
+pub struct MemberInfo {
+    /// Name of the member, e.g. `f_name` or `p_referenced_decl`
+    pub name: &'static str,
+    pub kind: MemberKind,
+    /// Lower case name of the node type declaring this member, e.g. `call_expr`
+    pub node_type: &'static str,
+    /// Parameters of the member. Syntax fields have none.
+    pub params: &'static [MemberParam],
+    /// Name of the raw result type, e.g. `ada_bool` or `ada_base_entity`
+    pub result_type: &'static str,
+}
+
+/// A dynamically typed value, used for the arguments and the results of members.
+pub enum Value {
+    Bool(bool),
+    Int(i32),
+    Node(Option<Node>),
+    Symbol(Symbol),
+    String(String),
+    BigInt(BigInt),
+    Unit(Option<Unit>),
+    /// Nodes, with `None` for null items
+    Nodes(Vec<Option<Node>>),
+    Units(Vec<Option<Unit>>),
+    Symbols(Vec<Symbol>),
+    RefdDecl(RefdDecl),
+    RefdDef(RefdDef),
+    Aspect(Aspect),
+    DiscreteRange(DiscreteRange),
+    ParamActuals(Vec<ParamActual>),
+    Shapes(Vec<Shape>),
+    RefResults(Vec<RefResult>),
+    ParamMatches(Vec<ParamMatch>),
+    CompletionItems(Vec<CompletionItem>),
+    DocAnnotations(Vec<DocAnnotation>),
+    Substitutions(Vec<Substitution>),
+}
+
+impl Value {
+    /// Name of the variant of this value, for error messages.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "Bool",
+            Self::Int(_) => "Int",
+            Self::Node(_) => "Node",
+            Self::Symbol(_) => "Symbol",
+            Self::String(_) => "String",
+            Self::BigInt(_) => "BigInt",
+            Self::Unit(_) => "Unit",
+            Self::Nodes(_) => "Nodes",
+            Self::Units(_) => "Units",
+            Self::Symbols(_) => "Symbols",
+            Self::RefdDecl(_) => "RefdDecl",
+            Self::RefdDef(_) => "RefdDef",
+            Self::Aspect(_) => "Aspect",
+            Self::DiscreteRange(_) => "DiscreteRange",
+            Self::ParamActuals(_) => "ParamActuals",
+            Self::Shapes(_) => "Shapes",
+            Self::RefResults(_) => "RefResults",
+            Self::ParamMatches(_) => "ParamMatches",
+            Self::CompletionItems(_) => "CompletionItems",
+            Self::DocAnnotations(_) => "DocAnnotations",
+            Self::Substitutions(_) => "Substitutions",
+        }
+    }
+}
+
+/// Storage for a raw argument or result, large enough for any supported type.
+#[repr(C)]
+union RawValue {
+    bool_: ada_bool,
+    int: core::ffi::c_int,
+    entity: ada_base_entity,
+    symbol: ada_symbol_type,
+    unit: ada_analysis_unit,
+    string: ada_string_type,
+    text: ManuallyDrop<ada_text>,
+    big_int: ada_big_integer,
+    array: *mut c_void,
+    refd_decl: ManuallyDrop<ada_internal_refd_decl>,
+    refd_def: ManuallyDrop<ada_internal_refd_def>,
+    aspect: ManuallyDrop<ada_internal_aspect>,
+    discrete_range: ManuallyDrop<ada_internal_discrete_range>,
+}
+
+/// Whether nodes of kind `kind` derive from the node type named `node_type`, e.g. `basic_decl`.
+pub(crate) fn derives_from(kind: NodeKind, node_type: &str) -> bool {
+    ada_node_types
+        .iter()
+        .find(|ty| ty.node_type == node_type)
+        .is_some_and(|ty| kind.is_a(ty.id))
+}
+
+fn property_info(property: &ada_node_property) -> MemberInfo {
+    MemberInfo {
+        name: property.name,
+        kind: MemberKind::Property,
+        node_type: property.node_type,
+        params: property.params,
+        result_type: property.result_type,
+    }
+}
+
+/// List the members available on nodes of kind `kind`, including inherited ones.
+pub fn members_of(kind: NodeKind) -> impl Iterator<Item = MemberInfo> {
+    let fields = ada_node_fields.iter().map(|field| MemberInfo {
+        name: field.name,
+        kind: MemberKind::Field,
+        node_type: field.node_type,
+        params: &[],
+        result_type: "ada_base_entity",
+    });
+    let properties = ada_node_properties.iter().map(property_info);
+
+    fields
+        .chain(properties)
+        .filter(move |member| derives_from(kind, member.node_type))
+}
+
+/// Return the default value of a property parameter, or `None` if it must be passed.
+///
+/// These are the defaults of libadalang's Ada and Python APIs, which the C API does not expose:
+///
+/// | Parameter                      | Default |
+/// |--------------------------------|---------|
+/// | `imprecise_fallback`           | `false` |
+/// | `origin`                       | null    |
+/// | `include_discriminants`        | `true`  |
+/// | `previous_parts_only`          | `false` |
+/// | `include_profile`              | `false` |
+/// | `follow_generic`               | `false` |
+/// | `only_inherited`               | `false` |
+/// | `include_predefined_operators` | `false` |
+pub fn param_default(param: &MemberParam) -> Option<Value> {
+    match param.name {
+        "imprecise_fallback"
+        | "previous_parts_only"
+        | "include_profile"
+        | "follow_generic"
+        | "only_inherited"
+        | "include_predefined_operators" => Some(Value::Bool(false)),
+        "include_discriminants" => Some(Value::Bool(true)),
+        "origin" => Some(Value::Node(None)),
+        _ => None,
+    }
+}
+
+/// Convert `value` to a raw argument of type `type_name`.
+fn raw_arg(type_name: &str, value: &Value) -> Result<RawValue> {
+    Ok(match (type_name, value) {
+        ("ada_bool", Value::Bool(b)) => RawValue {
+            bool_: *b as ada_bool,
+        },
+        (ty, Value::Int(i)) if ty.ends_with("c_int") => RawValue { int: *i },
+        ("ada_base_entity", Value::Node(None)) => RawValue {
+            entity: unsafe { core::mem::zeroed::<ada_base_entity>() },
+        },
+        ("ada_base_entity", Value::Node(Some(node))) => {
+            // libadalang does not detect stale bare nodes
+            node.check()?;
+            RawValue {
+                entity: node.as_raw(),
+            }
+        }
+        ("ada_symbol_type", Value::Symbol(symbol)) => RawValue {
+            symbol: symbol.as_raw(),
+        },
+        ("ada_analysis_unit", Value::Unit(None)) => RawValue {
+            unit: core::ptr::null_mut(),
+        },
+        ("ada_analysis_unit", Value::Unit(Some(unit))) => RawValue {
+            unit: unit.as_raw(),
+        },
+        (ty, value) => {
+            return Err(crate::Error::custom(format!(
+                "cannot pass a {} value as {ty}",
+                value.type_name()
+            )));
+        }
+    })
+}
+
+macro_rules! result_arrays {
+    ($($array:ident => $variant:ident,)*) => {
+        /// Array result types with a [`Value`] counterpart.
+        const ARRAY_RESULTS: &[&str] = &[$(stringify!($array)),*];
+
+        /// Convert a raw array of type `result_type`, taking ownership of it. Returns `None` for
+        /// other types.
+        ///
+        /// # Safety
+        /// The `raw` value must be a valid array of the given type, or a null pointer.
+        unsafe fn convert_array(result_type: &str, raw: *mut c_void) -> Option<Value> {
+            match result_type {
+                $(stringify!($array) => Some(Value::$variant(
+                    unsafe { LalArray::from_raw(libadalang_sys::$array::from_ptr(raw)) }
+                        .map(LalArray::into_vec)
+                        .unwrap_or_default(),
+                )),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+result_arrays! {
+    ada_ada_node_array => Nodes,
+    ada_base_formal_param_decl_array => Nodes,
+    ada_base_type_decl_array => Nodes,
+    ada_basic_decl_array => Nodes,
+    ada_compilation_unit_array => Nodes,
+    ada_defining_name_array => Nodes,
+    ada_expr_array => Nodes,
+    ada_generic_instantiation_array => Nodes,
+    ada_param_spec_array => Nodes,
+    ada_pragma_node_array => Nodes,
+    ada_type_decl_array => Nodes,
+    ada_analysis_unit_array => Units,
+    ada_unbounded_text_type_array => Symbols,
+    ada_internal_param_actual_array => ParamActuals,
+    ada_internal_shape_array => Shapes,
+    ada_internal_ref_result_array => RefResults,
+    ada_internal_param_match_array => ParamMatches,
+    ada_internal_completion_item_array => CompletionItems,
+    ada_internal_doc_annotation_array => DocAnnotations,
+    ada_internal_substitution_array => Substitutions,
+}
+
+/// Other result types with a [`Value`] counterpart, besides `c_int`.
+const SCALAR_RESULTS: &[&str] = &[
+    "ada_bool",
+    "ada_base_entity",
+    "ada_symbol_type",
+    "ada_analysis_unit",
+    "ada_text",
+    "ada_string_type",
+    "ada_big_integer",
+    "ada_internal_refd_decl",
+    "ada_internal_refd_def",
+    "ada_internal_aspect",
+    "ada_internal_discrete_range",
+];
+
+/// Convert a raw result of type `result_type`, taking ownership of it.
+///
+/// # Safety
+/// The `raw` value must hold a valid value of the given type.
+unsafe fn convert_result(result_type: &str, raw: RawValue) -> Result<Value> {
+    if let Some(value) = unsafe { convert_array(result_type, raw.array) } {
+        return Ok(value);
+    }
+
+    Ok(unsafe {
+        match result_type {
+            "ada_bool" => Value::Bool(raw.bool_ != 0),
+            ty if ty.ends_with("c_int") => Value::Int(raw.int),
+            "ada_base_entity" => Value::Node(Node::from_raw(raw.entity)),
+            "ada_symbol_type" => Value::Symbol(Symbol::from_raw(raw.symbol)),
+            "ada_analysis_unit" => {
+                Value::Unit((!raw.unit.is_null()).then(|| Unit::from_raw(raw.unit)))
+            }
+            "ada_text" => Value::String(text::take_string(ManuallyDrop::into_inner(raw.text))),
+            "ada_string_type" => {
+                let res = text::string_from_raw(raw.string);
+                if !raw.string.is_null() {
+                    ada_string_dec_ref(raw.string);
+                    Exception::log_and_ignore();
+                }
+                Value::String(res)
+            }
+            "ada_big_integer" => match BigInt::from_raw(raw.big_int) {
+                Some(big_int) => Value::BigInt(big_int),
+                None => return Err(crate::Error::custom("property returned a null big integer")),
+            },
+            "ada_internal_refd_decl" => {
+                Value::RefdDecl(RefdDecl::from_raw(ManuallyDrop::into_inner(raw.refd_decl)))
+            }
+            "ada_internal_refd_def" => {
+                Value::RefdDef(RefdDef::from_raw(ManuallyDrop::into_inner(raw.refd_def)))
+            }
+            "ada_internal_aspect" => {
+                Value::Aspect(Aspect::from_raw(ManuallyDrop::into_inner(raw.aspect)))
+            }
+            "ada_internal_discrete_range" => Value::DiscreteRange(DiscreteRange::from_raw(
+                ManuallyDrop::into_inner(raw.discrete_range),
+            )),
+            ty => {
+                return Err(crate::Error::custom(format!(
+                    "unsupported result type {ty}"
+                )));
+            }
+        }
+    })
+}
+
+impl MemberInfo {
+    /// Whether this member can be evaluated with [`Node::eval`], i.e. whether its parameter and
+    /// result types have a [`Value`] counterpart.
+    pub fn is_supported(&self) -> bool {
+        self.params.iter().all(|param| {
+            matches!(
+                param.type_name,
+                "ada_bool" | "ada_base_entity" | "ada_symbol_type" | "ada_analysis_unit"
+            ) || param.type_name.ends_with("c_int")
+        }) && (SCALAR_RESULTS.contains(&self.result_type)
+            || ARRAY_RESULTS.contains(&self.result_type)
+            || self.result_type.ends_with("c_int"))
+    }
+}
+
+impl Node {
+    /// List the syntax fields and properties of this node.
+    pub fn members(&self) -> impl Iterator<Item = MemberInfo> + use<> {
+        members_of(self.kind())
+    }
+
+    /// Evaluate the member named `name` (e.g. `f_name` or `p_referenced_decl`) on this node.
+    ///
+    /// Missing trailing arguments take their default value, see [`param_default`]. Parameters
+    /// without a default must be passed.
+    pub fn eval(&self, name: &str, args: &[Value]) -> Result<Value> {
+        self.check()?;
+        let kind = self.kind();
+
+        if let Some(field) = ada_node_fields
+            .iter()
+            .find(|field| field.name == name && derives_from(kind, field.node_type))
+        {
+            if !args.is_empty() {
+                return Err(crate::Error::custom(format!("{name} takes no arguments")));
+            }
+            return self
+                .field(field.name)?
+                .map(Value::Node)
+                .ok_or_else(|| crate::Error::custom(format!("cannot evaluate {name}")));
+        }
+
+        let Some(property) = ada_node_properties
+            .iter()
+            .find(|property| property.name == name && derives_from(kind, property.node_type))
+        else {
+            return Err(crate::Error::custom(format!(
+                "no member named {name} on {}",
+                self.kind_name()
+            )));
+        };
+
+        if args.len() > property.params.len() {
+            return Err(crate::Error::custom(format!(
+                "{name} takes {} argument(s), found {}",
+                property.params.len(),
+                args.len()
+            )));
+        }
+
+        if !property_info(property).is_supported() {
+            return Err(crate::Error::custom(format!(
+                "{name} has unsupported parameter or result types"
+            )));
+        }
+
+        let defaults = property.params[args.len()..]
+            .iter()
+            .map(|param| {
+                param_default(param).ok_or_else(|| {
+                    crate::Error::custom(format!("{name}: missing argument {}", param.name))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut raw_args = property
+            .params
+            .iter()
+            .zip(args.iter().chain(&defaults))
+            .map(|(param, arg)| raw_arg(param.type_name, arg))
+            .collect::<Result<Vec<_>>>()?;
+        let arg_ptrs = raw_args
+            .iter_mut()
+            .map(|arg| (arg as *mut RawValue).cast::<c_void>())
+            .collect::<Vec<_>>();
+
+        let mut result = RawValue {
+            array: core::ptr::null_mut(),
+        };
+        let node = self.as_ptr()?;
+        let found = unsafe { (property.thunk)(node, arg_ptrs.as_ptr(), (&raw mut result).cast()) };
+        // resolving names may request units, and call the event handler of the context
+        unit::resume_callback_panic(self.unit().as_raw());
+        Exception::wrap(())?;
+        if found == 0 {
+            return Err(crate::Error::custom(format!("cannot evaluate {name}")));
+        }
+
+        unsafe { convert_result(property.result_type, result) }
+    }
+}
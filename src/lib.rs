@@ -1,19 +1,48 @@
 //! High level Rust bindings for Libadalang
+//!
+//! # Thread safety
+//!
+//! An analysis context, with its units and nodes, must only be used by one thread: libadalang
+//! does not synchronize accesses to it, and its reference counts are not atomic. This is why
+//! [`Context`](analysis::Context), [`Unit`](analysis::Unit) and [`Node`](analysis::Node) are
+//! neither `Send` nor `Sync`. Different contexts can however be used concurrently, see
+//! [`ContextPool`](analysis::ContextPool).
+//!
+//! [`GprProject`](gpr_project::GprProject), [`FileReader`](file_reader::FileReader) and
+//! [`EventHandler`](event_handler::EventHandler) own their value until it is given to a context,
+//! so they are `Send`, but not `Sync`.
 
 pub mod analysis;
+pub mod array;
+pub mod big_int;
+pub mod call_graph;
+pub mod dependency_graph;
 pub mod diagnostic;
+pub mod driver;
 pub mod event_handler;
 pub mod exception;
 pub mod file_reader;
 pub mod gpr_project;
+pub mod introspection;
+pub mod query;
+pub mod rewriting;
+#[cfg(feature = "sarif")]
+pub mod sarif;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod structs;
+pub mod symbol;
 pub mod text;
+pub mod xref;
 
 use std::{
     ffi::{CString, c_char},
     fmt::Display,
 };
 
-use exception::Exception;
+use exception::{Exception, ExceptionKind};
+use query::QueryError;
+use rewriting::{ApplyError, TemplateError};
 
 pub(crate) fn ptr_or_null(opt: &Option<CString>) -> *const c_char {
     opt.as_ref()
@@ -21,9 +50,84 @@ pub(crate) fn ptr_or_null(opt: &Option<CString>) -> *const c_char {
         .unwrap_or(core::ptr::null())
 }
 
-pub enum Error {
-    Exception(Exception),
-    Custom(String),
+/// Result type of the functions of this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+macro_rules! errors {
+    ($($kind:ident => $variant:ident: $description:literal,)*) => {
+        /// Errors of this crate.
+        ///
+        /// Exceptions raised by libadalang are mapped to the variant of their kind.
+        #[derive(Debug)]
+        #[non_exhaustive]
+        pub enum Error {
+            $(
+                #[doc = concat!("Libadalang raised ", $description, ".")]
+                $variant(Exception),
+            )*
+            /// A query could not be parsed.
+            Query(QueryError),
+            /// A rewriting template does not match its arguments.
+            Template(TemplateError),
+            /// Applying a rewriting session produced invalid code.
+            Apply(ApplyError),
+            /// An I/O error, e.g. when saving or loading an index.
+            Io(std::io::Error),
+            Custom(String),
+        }
+
+        impl Error {
+            /// Return the libadalang exception of this error, if any.
+            pub fn exception(&self) -> Option<&Exception> {
+                match self {
+                    $(Self::$variant(e))|* => Some(e),
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<Exception> for Error {
+            fn from(value: Exception) -> Self {
+                match value.kind() {
+                    $(ExceptionKind::$kind => Self::$variant(value),)*
+                }
+            }
+        }
+
+        impl Display for Error {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $(Self::$variant(e) => write!(f, "{}: {}", $description, e.message()),)*
+                    Self::Query(_) => write!(f, "invalid query"),
+                    Self::Template(_) => write!(f, "invalid rewriting template"),
+                    Self::Apply(_) => write!(f, "rewriting produced invalid code"),
+                    Self::Io(_) => write!(f, "I/O error"),
+                    Self::Custom(msg) => write!(f, "{msg}"),
+                }
+            }
+        }
+    };
+}
+
+errors! {
+    FILE_READ_ERROR => FileReadError: "a file read error",
+    BAD_TYPE_ERROR => BadTypeError: "a bad type error",
+    OUT_OF_BOUNDS_ERROR => OutOfBoundsError: "an out of bounds error",
+    INVALID_INPUT => InvalidInput: "an invalid input error",
+    INVALID_SYMBOL_ERROR => InvalidSymbolError: "an invalid symbol error",
+    INVALID_UNIT_NAME_ERROR => InvalidUnitNameError: "an invalid unit name error",
+    NATIVE_EXCEPTION => NativeException: "a native exception",
+    PRECONDITION_FAILURE => PreconditionFailure: "a precondition failure",
+    PROPERTY_ERROR => PropertyError: "a property error",
+    TEMPLATE_ARGS_ERROR => TemplateArgsError: "a template arguments error",
+    TEMPLATE_FORMAT_ERROR => TemplateFormatError: "a template format error",
+    TEMPLATE_INSTANTIATION_ERROR => TemplateInstantiationError: "a template instantiation error",
+    STALE_REFERENCE_ERROR => StaleReference: "a stale reference error",
+    SYNTAX_ERROR => SyntaxError: "a syntax error",
+    UNKNOWN_CHARSET => UnknownCharset: "an unknown charset error",
+    MALFORMED_TREE_ERROR => MalformedTreeError: "a malformed tree error",
+    INVALID_PROJECT => InvalidProject: "an invalid project error",
+    UNSUPPORTED_VIEW_ERROR => UnsupportedViewError: "an unsupported view error",
 }
 
 impl Error {
@@ -32,9 +136,30 @@ impl Error {
     }
 }
 
-impl From<Exception> for Error {
-    fn from(value: Exception) -> Self {
-        Self::Exception(value)
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        if let Some(e) = self.exception() {
+            return Some(e);
+        }
+        match self {
+            Self::Query(e) => Some(e),
+            Self::Template(e) => Some(e),
+            Self::Apply(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// errors must be usable across threads, e.g. converted to `anyhow::Error`
+const _: () = {
+    fn check<T: Send + Sync + 'static>() {}
+    let _ = check::<Error>;
+};
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
     }
 }
 
@@ -0,0 +1,596 @@
+//! Structural queries over syntax trees
+//!
+//! A query is a pattern describing the shape of a subtree, compiled once with [`Query::new`] and
+//! then run over any node with [`Query::find_all`]. For example, the following query finds all
+//! calls to `Put_Line` and captures their argument list:
+//!
+//! ```text
+//! CallExpr(f_name: Identifier("Put_Line"), f_suffix: _ @args)
+//! ```
+//!
+//! # Syntax
+//!
+//! ```text
+//! pattern   ::= node [ '@' IDENT ]
+//! node      ::= ( KIND | '_' ) [ '(' [ predicate { ',' predicate } ] ')' ]
+//!             | 'null'
+//!             | STRING | REGEX
+//! predicate ::= FIELD ':' pattern
+//!             | STRING | REGEX
+//!             | '..' pattern
+//!             | pattern
+//! ```
+//!
+//! - `KIND` is the name of a node type, as returned by [`Node::kind_name`] (e.g. `CallExpr`),
+//!   and matches nodes deriving from it: abstract types such as `BasicDecl` are allowed. `_`
+//!   matches any node.
+//! - `null` only matches an absent node, and is useful in field constraints.
+//! - `FIELD ':' pattern` matches if the node has a syntax field named `FIELD` (e.g. `f_name`)
+//!   whose value matches `pattern`.
+//! - `"text"` matches if the text of the node is equal to `text`, ignoring case as Ada does.
+//!   Use `\"` and `\\` to escape quotes and backslashes.
+//! - `/regex/` matches if the text of the node matches the regular expression. Use `\/` to
+//!   escape slashes.
+//! - `'..' pattern` matches if any descendant of the node matches `pattern`.
+//! - A bare `pattern` matches if any direct child of the node matches `pattern`.
+//! - A lone `STRING` or `REGEX` is a shorthand for `_(STRING)` or `_(REGEX)`.
+//! - `'@' IDENT` captures the matched node under the name `IDENT`.
+
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use libadalang_sys::ada_node_fields;
+use regex::Regex;
+
+use crate::analysis::{Node, node::BaseKind};
+
+/// A compiled structural query.
+pub struct Query {
+    root: Pattern,
+}
+
+/// A successful match of a query.
+#[derive(Clone)]
+pub struct QueryMatch {
+    /// The node matched by the root pattern.
+    pub node: Node,
+    /// The nodes captured with `@name`, by name.
+    pub captures: HashMap<String, Node>,
+}
+
+impl QueryMatch {
+    /// Get a captured node by name.
+    pub fn capture(&self, name: &str) -> Option<&Node> {
+        self.captures.get(name)
+    }
+}
+
+/// Error raised when compiling an invalid query.
+#[derive(Debug, Clone)]
+pub struct QueryError {
+    /// Byte offset of the error in the query source.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid query at offset {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl Error for QueryError {}
+
+impl From<QueryError> for crate::Error {
+    fn from(value: QueryError) -> Self {
+        Self::Query(value)
+    }
+}
+
+struct Pattern {
+    kind: KindPattern,
+    predicates: Vec<Predicate>,
+    capture: Option<String>,
+}
+
+enum KindPattern {
+    Any,
+    Null,
+    Kind(BaseKind),
+}
+
+enum Predicate {
+    Field(String, Pattern),
+    /// Lower case text
+    Text(String),
+    Regex(Regex),
+    Child(Pattern),
+    Descendant(Pattern),
+}
+
+impl Query {
+    /// Compile a query from its source.
+    pub fn new(source: &str) -> crate::Result<Self> {
+        let mut parser = Parser {
+            tokens: lex(source)?,
+            pos: 0,
+            end: source.len(),
+        };
+        let root = parser.pattern()?;
+        if let Some((offset, _)) = parser.peek() {
+            return Err(QueryError {
+                offset,
+                message: "unexpected trailing input".into(),
+            }
+            .into());
+        }
+        Ok(Self { root })
+    }
+
+    /// Match the query against `node` only.
+    pub fn match_node(&self, node: &Node) -> Option<QueryMatch> {
+        let mut captures = HashMap::new();
+        let matched = self.root.matches(Some(node), &mut captures);
+        matched.then_some(QueryMatch {
+            node: *node,
+            captures,
+        })
+    }
+
+    /// Find all the matches of the query in the subtree rooted at `root`, in prefix order, or
+    /// fail if `root` is stale. Nodes that become stale during the iteration do not match.
+    pub fn find_all<'a>(
+        &'a self,
+        root: &Node,
+    ) -> crate::Result<impl Iterator<Item = QueryMatch> + 'a> {
+        Ok(root.traverse()?.filter_map(|node| self.match_node(&node)))
+    }
+}
+
+impl Pattern {
+    /// Match this pattern against a possibly null node, adding captures to `captures` on success.
+    fn matches(&self, node: Option<&Node>, captures: &mut HashMap<String, Node>) -> bool {
+        let node = match (&self.kind, node) {
+            (KindPattern::Null, None) => return true,
+            (KindPattern::Null, Some(_)) | (_, None) => return false,
+            (KindPattern::Kind(kind), Some(node)) if !node.is_a(*kind) => return false,
+            (_, Some(node)) => node,
+        };
+
+        // only commit captures once the whole pattern matched
+        let mut local = captures.clone();
+        for predicate in &self.predicates {
+            if !predicate.matches(node, &mut local) {
+                return false;
+            }
+        }
+
+        if let Some(name) = &self.capture {
+            local.insert(name.clone(), *node);
+        }
+        *captures = local;
+        true
+    }
+}
+
+impl Predicate {
+    fn matches(&self, node: &Node, captures: &mut HashMap<String, Node>) -> bool {
+        match self {
+            Predicate::Field(name, pattern) => match node.field(name) {
+                Ok(Some(value)) => pattern.matches(value.as_ref(), captures),
+                Ok(None) | Err(_) => false,
+            },
+            Predicate::Text(text) => node.text().is_ok_and(|t| t.to_lowercase() == *text),
+            Predicate::Regex(regex) => node.text().is_ok_and(|t| regex.is_match(&t)),
+            Predicate::Child(pattern) => node
+                .children()
+                .into_iter()
+                .flatten()
+                .any(|child| pattern.matches(Some(&child), captures)),
+            Predicate::Descendant(pattern) => node
+                .traverse()
+                .into_iter()
+                .flatten()
+                .skip(1)
+                .any(|child| pattern.matches(Some(&child), captures)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Regex(String),
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    At,
+    DotDot,
+}
+
+/// Read a delimited literal starting after its opening delimiter at `start`. `\` escapes the
+/// delimiter and itself; for regexes, other escapes are kept as is.
+fn lex_delimited(
+    source: &str,
+    start: usize,
+    delim: char,
+    keep_escapes: bool,
+) -> Result<(String, usize), QueryError> {
+    let mut res = String::new();
+    let mut chars = source[start + 1..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) if c == delim || c == '\\' && !keep_escapes => res.push(c),
+                Some((_, c)) => {
+                    res.push('\\');
+                    res.push(c);
+                }
+                None => break,
+            },
+            c if c == delim => return Ok((res, start + 1 + i + 1)),
+            c => res.push(c),
+        }
+    }
+
+    Err(QueryError {
+        offset: start,
+        message: format!("unterminated literal, expected closing `{delim}`"),
+    })
+}
+
+fn lex(source: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(c) = source[pos..].chars().next() {
+        let start = pos;
+        let token = match c {
+            c if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '@' => Token::At,
+            '.' if source[pos..].starts_with("..") => {
+                pos += 1;
+                Token::DotDot
+            }
+            '"' => {
+                let (text, end) = lex_delimited(source, pos, '"', false)?;
+                tokens.push((start, Token::Str(text)));
+                pos = end;
+                continue;
+            }
+            '/' => {
+                let (text, end) = lex_delimited(source, pos, '/', true)?;
+                tokens.push((start, Token::Regex(text)));
+                pos = end;
+                continue;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let len = source[pos..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(source.len() - pos);
+                tokens.push((start, Token::Ident(source[pos..pos + len].to_owned())));
+                pos += len;
+                continue;
+            }
+            c => {
+                return Err(QueryError {
+                    offset: pos,
+                    message: format!("unexpected character `{c}`"),
+                });
+            }
+        };
+        tokens.push((start, token));
+        pos += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Length of the source, used as the offset of errors at the end of the input.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.pos)
+            .map(|(offset, tok)| (*offset, tok))
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + n).map(|(_, tok)| tok)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), QueryError> {
+        let res = self.tokens.get(self.pos).cloned().ok_or(QueryError {
+            offset: self.end,
+            message: "unexpected end of query".into(),
+        })?;
+        self.pos += 1;
+        Ok(res)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), QueryError> {
+        let (offset, tok) = self.next()?;
+        if tok == expected {
+            Ok(())
+        } else {
+            Err(QueryError {
+                offset,
+                message: format!("expected {expected:?}, found {tok:?}"),
+            })
+        }
+    }
+
+    fn regex(offset: usize, source: &str) -> Result<Regex, QueryError> {
+        Regex::new(source).map_err(|e| QueryError {
+            offset,
+            message: e.to_string(),
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, QueryError> {
+        let (offset, tok) = self.next()?;
+        let mut pattern = match tok {
+            Token::Ident(ident) if ident == "null" => Pattern {
+                kind: KindPattern::Null,
+                predicates: Vec::new(),
+                capture: None,
+            },
+            Token::Ident(ident) => {
+                let kind = if ident == "_" {
+                    KindPattern::Any
+                } else {
+                    KindPattern::Kind(BaseKind::from_name(&ident).ok_or_else(|| QueryError {
+                        offset,
+                        message: format!("unknown node kind `{ident}`"),
+                    })?)
+                };
+                let predicates = if self.peek_nth(0) == Some(&Token::LParen) {
+                    self.predicates()?
+                } else {
+                    Vec::new()
+                };
+                Pattern {
+                    kind,
+                    predicates,
+                    capture: None,
+                }
+            }
+            Token::Str(text) => Pattern {
+                kind: KindPattern::Any,
+                predicates: vec![Predicate::Text(text.to_lowercase())],
+                capture: None,
+            },
+            Token::Regex(source) => Pattern {
+                kind: KindPattern::Any,
+                predicates: vec![Predicate::Regex(Self::regex(offset, &source)?)],
+                capture: None,
+            },
+            tok => {
+                return Err(QueryError {
+                    offset,
+                    message: format!("expected a pattern, found {tok:?}"),
+                });
+            }
+        };
+
+        if self.peek_nth(0) == Some(&Token::At) {
+            self.next()?;
+            match self.next()? {
+                (_, Token::Ident(name)) => pattern.capture = Some(name),
+                (offset, tok) => {
+                    return Err(QueryError {
+                        offset,
+                        message: format!("expected a capture name, found {tok:?}"),
+                    });
+                }
+            }
+        }
+
+        Ok(pattern)
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Predicate>, QueryError> {
+        self.expect(Token::LParen)?;
+        let mut predicates = Vec::new();
+
+        if self.peek_nth(0) == Some(&Token::RParen) {
+            self.next()?;
+            return Ok(predicates);
+        }
+
+        loop {
+            predicates.push(self.predicate()?);
+            match self.next()? {
+                (_, Token::Comma) => continue,
+                (_, Token::RParen) => break,
+                (offset, tok) => {
+                    return Err(QueryError {
+                        offset,
+                        message: format!("expected `,` or `)`, found {tok:?}"),
+                    });
+                }
+            }
+        }
+
+        Ok(predicates)
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, QueryError> {
+        match (self.peek(), self.peek_nth(1)) {
+            (Some((offset, Token::Ident(field))), Some(Token::Colon)) => {
+                let field = field.clone();
+                if !ada_node_fields.iter().any(|f| f.name == field) {
+                    return Err(QueryError {
+                        offset,
+                        message: format!("unknown syntax field `{field}`"),
+                    });
+                }
+                self.pos += 2;
+                Ok(Predicate::Field(field, self.pattern()?))
+            }
+            (Some((_, Token::Str(text))), _) => {
+                let text = text.to_lowercase();
+                self.pos += 1;
+                Ok(Predicate::Text(text))
+            }
+            (Some((offset, Token::Regex(source))), _) => {
+                let regex = Self::regex(offset, source)?;
+                self.pos += 1;
+                Ok(Predicate::Regex(regex))
+            }
+            (Some((_, Token::DotDot)), _) => {
+                self.pos += 1;
+                Ok(Predicate::Descendant(self.pattern()?))
+            }
+            _ => Ok(Predicate::Child(self.pattern()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        lex(source)
+            .unwrap()
+            .into_iter()
+            .map(|(_, tok)| tok)
+            .collect()
+    }
+
+    fn error(source: &str) -> QueryError {
+        match Query::new(source) {
+            Err(crate::Error::Query(err)) => err,
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("`{source}` should not compile"),
+        }
+    }
+
+    #[test]
+    fn lex_tokens() {
+        assert_eq!(
+            tokens(r#"CallExpr(f_name: _ @n, .. "x", /y/)"#),
+            vec![
+                Token::Ident("CallExpr".into()),
+                Token::LParen,
+                Token::Ident("f_name".into()),
+                Token::Colon,
+                Token::Ident("_".into()),
+                Token::At,
+                Token::Ident("n".into()),
+                Token::Comma,
+                Token::DotDot,
+                Token::Str("x".into()),
+                Token::Comma,
+                Token::Regex("y".into()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_offsets() {
+        let offsets = lex("  _ ( \"é\" )")
+            .unwrap()
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [2, 4, 6, 11]);
+    }
+
+    #[test]
+    fn lex_escapes() {
+        assert_eq!(tokens(r#""a\"b\\c\n""#), [Token::Str(r#"a"b\c\n"#.into())]);
+        // regexes keep their escapes, except for the delimiter
+        assert_eq!(tokens(r"/a\/b\d\\/"), [Token::Regex(r"a/b\d\\".into())]);
+    }
+
+    #[test]
+    fn lex_errors() {
+        let err = lex("_(\"abc").unwrap_err();
+        assert_eq!(err.offset, 2);
+        assert!(err.message.contains("unterminated"));
+
+        let err = lex("_ # _").unwrap_err();
+        assert_eq!(err.offset, 2);
+        assert!(err.message.contains('#'));
+
+        // a single dot is not an operator
+        assert_eq!(lex("_(.)").unwrap_err().offset, 2);
+    }
+
+    #[test]
+    fn parse_pattern() {
+        let query =
+            Query::new(r#"CallExpr(f_name: "Put_Line" @name, f_suffix: null, .. /x/, _) @call"#)
+                .unwrap();
+        let root = &query.root;
+        assert!(matches!(root.kind, KindPattern::Kind(kind) if kind == BaseKind::CALL_EXPR));
+        assert_eq!(root.capture.as_deref(), Some("call"));
+
+        match root.predicates.as_slice() {
+            [
+                Predicate::Field(name, text),
+                Predicate::Field(suffix, null),
+                Predicate::Descendant(descendant),
+                Predicate::Child(child),
+            ] => {
+                assert_eq!(name, "f_name");
+                assert_eq!(text.capture.as_deref(), Some("name"));
+                assert!(
+                    matches!(text.predicates.as_slice(), [Predicate::Text(t)] if t == "put_line")
+                );
+                assert_eq!(suffix, "f_suffix");
+                assert!(matches!(null.kind, KindPattern::Null));
+                assert!(
+                    matches!(descendant.predicates.as_slice(), [Predicate::Regex(r)] if r.as_str() == "x")
+                );
+                assert!(matches!(child.kind, KindPattern::Any));
+                assert!(child.predicates.is_empty());
+            }
+            _ => panic!("unexpected predicates"),
+        }
+    }
+
+    #[test]
+    fn parse_empty_predicates() {
+        assert!(Query::new("_()").unwrap().root.predicates.is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = error("Foo");
+        assert_eq!(err.offset, 0);
+        assert!(err.message.contains("unknown node kind"));
+
+        let err = error("_(f_nope: _)");
+        assert_eq!(err.offset, 2);
+        assert!(err.message.contains("unknown syntax field"));
+
+        assert_eq!(error("_ _").offset, 2);
+        assert_eq!(error("_(_").offset, 3);
+        assert_eq!(error("_ @ (").offset, 4);
+        assert_eq!(error("_(_ _)").offset, 4);
+        assert_eq!(error("_(/(/)").offset, 2);
+        assert_eq!(error(")").offset, 0);
+    }
+}
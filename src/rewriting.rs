@@ -0,0 +1,567 @@
+//! Tree rewriting
+//!
+//! A [`RewritingSession`] is started on an analysis context with [`Context::start_rewriting`].
+//! Node and unit handles borrow the session, so that they cannot be used once the session has
+//! been applied or aborted.
+
+mod diff;
+mod template;
+
+pub use diff::unified_diff;
+pub use template::{TemplateArg, TemplateError};
+
+use std::{error::Error, ffi::c_int, fmt::Display, marker::PhantomData, mem::MaybeUninit};
+
+use libadalang_sys::{
+    ada_free, ada_introspection_member_ref, ada_node_rewriting_handle,
+    ada_rewriting_abort_rewriting, ada_rewriting_apply, ada_rewriting_apply_result,
+    ada_rewriting_child, ada_rewriting_children_count, ada_rewriting_clone,
+    ada_rewriting_create_from_template, ada_rewriting_create_node,
+    ada_rewriting_create_regular_node, ada_rewriting_create_token_node, ada_rewriting_first_child,
+    ada_rewriting_free_apply_result, ada_rewriting_handle, ada_rewriting_handle_to_node,
+    ada_rewriting_handle_to_unit, ada_rewriting_insert_after, ada_rewriting_insert_before,
+    ada_rewriting_insert_first, ada_rewriting_insert_last, ada_rewriting_is_list_node,
+    ada_rewriting_kind, ada_rewriting_last_child, ada_rewriting_next_child,
+    ada_rewriting_node_image, ada_rewriting_node_to_handle, ada_rewriting_node_unparse,
+    ada_rewriting_parent, ada_rewriting_previous_child, ada_rewriting_remove_child,
+    ada_rewriting_replace, ada_rewriting_set_child, ada_rewriting_set_text,
+    ada_rewriting_start_rewriting, ada_rewriting_text, ada_rewriting_tied,
+    ada_rewriting_unit_handles, ada_rewriting_unit_root, ada_rewriting_unit_set_root,
+    ada_rewriting_unit_to_handle, ada_rewriting_unit_unparse, ada_unit_rewriting_handle,
+};
+
+use crate::{
+    Result,
+    analysis::{Context, GrammarRule, Node, Unit, node::NodeKind},
+    diagnostic::Diagnostic,
+    exception::Exception,
+    text::{self, Text},
+};
+
+/// Reference to a node member (syntax field or property), used to designate the children of a
+/// node.
+pub type MemberRef = ada_introspection_member_ref;
+
+/// A rewriting session on an analysis context.
+///
+/// There can be at most one session per context. The session is aborted when dropped, unless it
+/// was successfully applied.
+pub struct RewritingSession<'ctx> {
+    raw: ada_rewriting_handle,
+    _context: PhantomData<&'ctx Context>,
+}
+
+/// Handle to an analysis unit in a rewriting session.
+#[derive(Clone, Copy)]
+pub struct UnitRewriter<'s> {
+    raw: ada_unit_rewriting_handle,
+    _session: PhantomData<&'s ()>,
+}
+
+/// Handle to a node in a rewriting session. The node is either tied to an analysis unit, or was
+/// created in the session.
+#[derive(Clone, Copy)]
+pub struct NodeRewriter<'s> {
+    raw: ada_node_rewriting_handle,
+    _session: PhantomData<&'s ()>,
+}
+
+/// Error raised when applying a rewriting session yields invalid trees.
+pub struct ApplyError {
+    /// Name of the file of the unit that could not be reparsed
+    pub filename: String,
+    /// The reparsing diagnostics for this unit
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A failed [`RewritingSession::apply`]. The session is left untouched, and can be fixed and
+/// applied again.
+pub struct ApplyFailure<'ctx> {
+    pub session: RewritingSession<'ctx>,
+    /// [`Error::Apply`](crate::Error::Apply) if the trees were invalid, or the exception raised
+    /// by libadalang
+    pub error: crate::Error,
+}
+
+impl Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rewriting produced invalid code in {}", self.filename)?;
+        for diag in &self.diagnostics {
+            write!(f, "\n{diag}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ApplyError {}
+
+impl From<ApplyError> for crate::Error {
+    fn from(value: ApplyError) -> Self {
+        Self::Apply(value)
+    }
+}
+
+impl From<ApplyFailure<'_>> for crate::Error {
+    fn from(value: ApplyFailure<'_>) -> Self {
+        value.error
+    }
+}
+
+fn node_handle<'s>(raw: ada_node_rewriting_handle) -> Option<NodeRewriter<'s>> {
+    (!raw.is_null()).then_some(NodeRewriter {
+        raw,
+        _session: PhantomData,
+    })
+}
+
+/// Error for a function that returned a null handle without raising an exception.
+fn created_null(what: &str) -> crate::Error {
+    crate::Error::custom(format!("libadalang returned a null rewriting {what}"))
+}
+
+fn raw_or_null(node: Option<NodeRewriter<'_>>) -> ada_node_rewriting_handle {
+    node.map(|n| n.raw).unwrap_or(core::ptr::null_mut())
+}
+
+impl Context {
+    /// Start a rewriting session on this context.
+    ///
+    /// Fails if a session is already in progress.
+    pub fn start_rewriting(&self) -> Result<RewritingSession<'_>> {
+        let raw = unsafe { ada_rewriting_start_rewriting(self.as_raw()) };
+        let raw = Exception::wrap(raw)?;
+        if raw.is_null() {
+            return Err(created_null("session"));
+        }
+        Ok(RewritingSession {
+            raw,
+            _context: PhantomData,
+        })
+    }
+}
+
+impl<'ctx> RewritingSession<'ctx> {
+    /// Get the inner raw rewriting handle.
+    pub fn as_raw(&self) -> ada_rewriting_handle {
+        self.raw
+    }
+
+    /// Get the rewriting handle of an analysis unit of the context.
+    pub fn unit(&self, unit: &Unit) -> Result<UnitRewriter<'_>> {
+        let raw = unsafe { ada_rewriting_unit_to_handle(unit.as_raw()) };
+        let raw = Exception::wrap(raw)?;
+        if raw.is_null() {
+            return Err(created_null("unit handle"));
+        }
+        Ok(UnitRewriter {
+            raw,
+            _session: PhantomData,
+        })
+    }
+
+    /// Get the rewriting handle of a node of the context.
+    ///
+    /// Fails if the node is stale.
+    pub fn node(&self, node: &Node) -> Result<NodeRewriter<'_>> {
+        node.check()?;
+        let raw = unsafe { ada_rewriting_node_to_handle(node.as_raw().node) };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node handle"))
+    }
+
+    /// Return the handles of the units that have been modified in this session.
+    pub fn modified_units(&self) -> Vec<UnitRewriter<'_>> {
+        let ptr = unsafe { ada_rewriting_unit_handles(self.raw) };
+        if ptr.is_null() {
+            return Vec::new();
+        }
+
+        let mut res = Vec::new();
+        // the array is null-terminated
+        for i in 0.. {
+            let raw = unsafe { *ptr.add(i) };
+            if raw.is_null() {
+                break;
+            }
+            res.push(UnitRewriter {
+                raw,
+                _session: PhantomData,
+            });
+        }
+
+        unsafe { ada_free(ptr.cast()) };
+        res
+    }
+
+    /// Return the new source text of each unit modified in this session, without applying the
+    /// modifications.
+    pub fn unparse_units(&self) -> Result<Vec<(Unit, String)>> {
+        self.modified_units()
+            .into_iter()
+            .map(|u| Ok((u.unit(), u.unparse()?)))
+            .collect()
+    }
+
+    /// Return a unified diff of all the units modified in this session, against their original
+    /// source.
+    pub fn diff(&self) -> Result<String> {
+        self.modified_units()
+            .into_iter()
+            .map(|u| u.diff())
+            .collect()
+    }
+
+    /// Create a new node of the given kind, with null children or an empty list.
+    pub fn create_node(&self, kind: NodeKind) -> Result<NodeRewriter<'_>> {
+        let raw = unsafe { ada_rewriting_create_node(self.raw, kind) };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node"))
+    }
+
+    /// Create a new token node of the given kind, with the given text.
+    pub fn create_token_node(&self, kind: NodeKind, text: &str) -> Result<NodeRewriter<'_>> {
+        let text = Text::new(text);
+        let mut raw_text = text.as_raw_borrow();
+        let raw = unsafe { ada_rewriting_create_token_node(self.raw, kind, &raw mut raw_text) };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node"))
+    }
+
+    /// Create a new regular node of the given kind, with the given children.
+    ///
+    /// The children must not be tied to a unit or to another node.
+    pub fn create_regular_node<'s>(
+        &'s self,
+        kind: NodeKind,
+        children: &[Option<NodeRewriter<'s>>],
+    ) -> Result<NodeRewriter<'s>> {
+        let mut children = children.iter().map(|&c| raw_or_null(c)).collect::<Vec<_>>();
+        let raw = unsafe {
+            ada_rewriting_create_regular_node(
+                self.raw,
+                kind,
+                children.as_mut_ptr(),
+                children.len() as c_int,
+            )
+        };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node"))
+    }
+
+    /// Create a tree of new nodes by parsing `template` with the given grammar rule.
+    ///
+    /// Each `{}` placeholder in the template is replaced by the corresponding argument: nodes
+    /// are inserted in the created tree, and must not be tied, while text arguments are spliced
+    /// in the source before parsing. `{{` and `}}` are escapes for `{` and `}`.
+    ///
+    /// ```no_run
+    /// # use libadalang::{analysis::GrammarRule, rewriting::RewritingSession};
+    /// # fn f(session: &RewritingSession, node: &libadalang::analysis::Node) -> libadalang::Result<()> {
+    /// let expr = session.clone_node(node)?;
+    /// let stmt = session.create_from_template(
+    ///     "X := {} + {};",
+    ///     &[expr.into(), "1".into()],
+    ///     GrammarRule::STMT,
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_from_template<'s>(
+        &'s self,
+        template: &str,
+        arguments: &[TemplateArg<'s, '_>],
+        rule: GrammarRule,
+    ) -> Result<NodeRewriter<'s>> {
+        let (template, mut arguments) = template::expand(template, arguments)?;
+        let template = Text::new(&template);
+        let mut raw_template = template.as_raw_borrow();
+        let raw = unsafe {
+            ada_rewriting_create_from_template(
+                self.raw,
+                &raw mut raw_template,
+                arguments.as_mut_ptr(),
+                arguments.len() as c_int,
+                rule,
+            )
+        };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node"))
+    }
+
+    /// Create an untied copy of a node of the context, e.g. to use it as a template argument.
+    pub fn clone_node(&self, node: &Node) -> Result<NodeRewriter<'_>> {
+        self.node(node)?.deep_clone()
+    }
+
+    /// Apply all the modifications of this session to the analysis context.
+    ///
+    /// On success, the session is closed and the modified units are returned. Otherwise, the
+    /// session is returned along with the reparsing errors.
+    ///
+    /// The modified units are reparsed, so their nodes become stale.
+    pub fn apply(self) -> Result<Vec<Unit>, ApplyFailure<'ctx>> {
+        let units = self
+            .modified_units()
+            .into_iter()
+            .map(|u| u.unit())
+            .collect::<Vec<_>>();
+
+        let mut result = MaybeUninit::<ada_rewriting_apply_result>::uninit();
+        unsafe { ada_rewriting_apply(self.raw, result.as_mut_ptr()) };
+        if let Err(exc) = Exception::wrap(()) {
+            // `result` is not initialized, and the session is still open
+            return Err(ApplyFailure {
+                session: self,
+                error: exc.into(),
+            });
+        }
+        let mut result = unsafe { result.assume_init() };
+
+        if result.success != 0 {
+            // the session is closed by libadalang
+            core::mem::forget(self);
+            for unit in &units {
+                crate::analysis::unit::bump_version(unit.as_raw());
+            }
+            return Ok(units);
+        }
+
+        let count = usize::try_from(result.diagnostics_count).unwrap_or(0);
+        let filename = unsafe { Unit::from_raw(result.unit) }.filename();
+        let diagnostics = (0..count)
+            .map(|i| {
+                Diagnostic::from_raw(unsafe { core::ptr::read(result.diagnostics.add(i)) })
+                    .with_filename(&filename)
+            })
+            .collect();
+        unsafe { ada_rewriting_free_apply_result(&raw mut result) };
+
+        Err(ApplyFailure {
+            session: self,
+            error: ApplyError {
+                filename,
+                diagnostics,
+            }
+            .into(),
+        })
+    }
+
+    /// Discard all the modifications of this session.
+    pub fn abort(self) {
+        drop(self)
+    }
+}
+
+impl Drop for RewritingSession<'_> {
+    fn drop(&mut self) {
+        unsafe { ada_rewriting_abort_rewriting(self.raw) };
+        Exception::log_and_ignore();
+    }
+}
+
+impl<'s> UnitRewriter<'s> {
+    /// Get the inner raw unit rewriting handle.
+    pub fn as_raw(&self) -> ada_unit_rewriting_handle {
+        self.raw
+    }
+
+    /// Return the analysis unit of this handle.
+    pub fn unit(&self) -> Unit {
+        unsafe { Unit::from_raw(ada_rewriting_handle_to_unit(self.raw)) }
+    }
+
+    /// Return the root node of this unit, if any.
+    pub fn root(&self) -> Option<NodeRewriter<'s>> {
+        node_handle(unsafe { ada_rewriting_unit_root(self.raw) })
+    }
+
+    /// Set the root node of this unit. The new root must not be tied to another unit.
+    pub fn set_root(&self, root: Option<NodeRewriter<'s>>) -> Result<()> {
+        unsafe { ada_rewriting_unit_set_root(self.raw, raw_or_null(root)) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Return the source code for this unit, with the modifications of the session.
+    pub fn unparse(&self) -> Result<String> {
+        let mut text = text::empty_raw();
+        unsafe { ada_rewriting_unit_unparse(self.raw, &raw mut text) };
+        Ok(Exception::wrap(text::take_string(text))?)
+    }
+
+    /// Return a unified diff from the original source of this unit to its rewritten source.
+    ///
+    /// The original source is read from the unit's file when possible, and falls back to the
+    /// text that was parsed otherwise (e.g. for units parsed from a buffer).
+    pub fn diff(&self) -> Result<String> {
+        let unit = self.unit();
+        let filename = unit.filename();
+        let original = match std::fs::read_to_string(&filename) {
+            Ok(content) => content,
+            Err(_) => unit.unparse()?,
+        };
+
+        Ok(unified_diff(
+            &original,
+            &self.unparse()?,
+            &format!("a/{filename}"),
+            &format!("b/{filename}"),
+        ))
+    }
+}
+
+impl<'s> NodeRewriter<'s> {
+    /// Get the inner raw node rewriting handle.
+    pub fn as_raw(&self) -> ada_node_rewriting_handle {
+        self.raw
+    }
+
+    /// Return the original node of this handle, or `None` for nodes created in the session.
+    pub fn node(&self) -> Option<Node> {
+        unsafe { Node::from_bare(ada_rewriting_handle_to_node(self.raw)) }
+    }
+
+    /// Return the kind of this node.
+    pub fn kind(&self) -> NodeKind {
+        unsafe { ada_rewriting_kind(self.raw) }
+    }
+
+    /// Return a debug representation of this node.
+    pub fn image(&self) -> String {
+        let mut text = text::empty_raw();
+        unsafe { ada_rewriting_node_image(self.raw, &raw mut text) };
+        text::take_string(text)
+    }
+
+    /// Return the source code for this node, with the modifications of the session.
+    pub fn unparse(&self) -> Result<String> {
+        let mut text = text::empty_raw();
+        unsafe { ada_rewriting_node_unparse(self.raw, &raw mut text) };
+        Ok(Exception::wrap(text::take_string(text))?)
+    }
+
+    /// Return whether this node is tied to an analysis unit (i.e. it is part of a tree).
+    pub fn is_tied(&self) -> bool {
+        unsafe { ada_rewriting_tied(self.raw) != 0 }
+    }
+
+    /// Return the parent of this node, if it is tied to a parent node.
+    pub fn parent(&self) -> Option<NodeRewriter<'s>> {
+        node_handle(unsafe { ada_rewriting_parent(self.raw) })
+    }
+
+    /// Return the number of children of this node.
+    pub fn children_count(&self) -> usize {
+        usize::try_from(unsafe { ada_rewriting_children_count(self.raw) }).unwrap_or(0)
+    }
+
+    /// Return the child of this node designated by the syntax field `field`.
+    pub fn child(&self, field: MemberRef) -> Result<Option<NodeRewriter<'s>>> {
+        let raw = unsafe { ada_rewriting_child(self.raw, field) };
+        Ok(node_handle(Exception::wrap(raw)?))
+    }
+
+    /// Set the child of this node designated by the syntax field `field`. The new child must not
+    /// be tied to another node.
+    pub fn set_child(&self, field: MemberRef, child: Option<NodeRewriter<'s>>) -> Result<()> {
+        unsafe { ada_rewriting_set_child(self.raw, field, raw_or_null(child)) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Return the text of this token node.
+    pub fn text(&self) -> Result<String> {
+        let mut text = text::empty_raw();
+        unsafe { ada_rewriting_text(self.raw, &raw mut text) };
+        Ok(Exception::wrap(text::take_string(text))?)
+    }
+
+    /// Replace the text of this token node.
+    pub fn set_text(&self, text: &str) -> Result<()> {
+        let text = Text::new(text);
+        let mut raw_text = text.as_raw_borrow();
+        unsafe { ada_rewriting_set_text(self.raw, &raw mut raw_text) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Replace this node by `new_node` in its parent. The new node must not be tied.
+    pub fn replace(&self, new_node: Option<NodeRewriter<'s>>) -> Result<()> {
+        unsafe { ada_rewriting_replace(self.raw, raw_or_null(new_node)) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Create an untied copy of the subtree rooted at this node.
+    pub fn deep_clone(&self) -> Result<NodeRewriter<'s>> {
+        let raw = unsafe { ada_rewriting_clone(self.raw) };
+        node_handle(Exception::wrap(raw)?).ok_or_else(|| created_null("node"))
+    }
+
+    /// Return whether this node is a list node.
+    pub fn is_list(&self) -> bool {
+        unsafe { ada_rewriting_is_list_node(self.raw) != 0 }
+    }
+
+    /// Return the first child of this list node.
+    pub fn first_child(&self) -> Result<Option<NodeRewriter<'s>>> {
+        let raw = unsafe { ada_rewriting_first_child(self.raw) };
+        Ok(node_handle(Exception::wrap(raw)?))
+    }
+
+    /// Return the last child of this list node.
+    pub fn last_child(&self) -> Result<Option<NodeRewriter<'s>>> {
+        let raw = unsafe { ada_rewriting_last_child(self.raw) };
+        Ok(node_handle(Exception::wrap(raw)?))
+    }
+
+    /// Return the next sibling of this node in its parent list node.
+    pub fn next_child(&self) -> Result<Option<NodeRewriter<'s>>> {
+        let raw = unsafe { ada_rewriting_next_child(self.raw) };
+        Ok(node_handle(Exception::wrap(raw)?))
+    }
+
+    /// Return the previous sibling of this node in its parent list node.
+    pub fn previous_child(&self) -> Result<Option<NodeRewriter<'s>>> {
+        let raw = unsafe { ada_rewriting_previous_child(self.raw) };
+        Ok(node_handle(Exception::wrap(raw)?))
+    }
+
+    /// Iterate over the children of this list node.
+    pub fn list_children(&self) -> Result<Vec<NodeRewriter<'s>>> {
+        let mut res = Vec::new();
+        let mut child = self.first_child()?;
+        while let Some(c) = child {
+            res.push(c);
+            child = c.next_child()?;
+        }
+        Ok(res)
+    }
+
+    /// Insert `sibling` before this node in its parent list node.
+    pub fn insert_before(&self, sibling: NodeRewriter<'s>) -> Result<()> {
+        unsafe { ada_rewriting_insert_before(self.raw, sibling.raw) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Insert `sibling` after this node in its parent list node.
+    pub fn insert_after(&self, sibling: NodeRewriter<'s>) -> Result<()> {
+        unsafe { ada_rewriting_insert_after(self.raw, sibling.raw) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Insert `child` at the beginning of this list node.
+    pub fn insert_first(&self, child: NodeRewriter<'s>) -> Result<()> {
+        unsafe { ada_rewriting_insert_first(self.raw, child.raw) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Insert `child` at the end of this list node.
+    pub fn insert_last(&self, child: NodeRewriter<'s>) -> Result<()> {
+        unsafe { ada_rewriting_insert_last(self.raw, child.raw) };
+        Ok(Exception::wrap(())?)
+    }
+
+    /// Remove this node from its parent list node.
+    pub fn remove(&self) -> Result<()> {
+        unsafe { ada_rewriting_remove_child(self.raw) };
+        Ok(Exception::wrap(())?)
+    }
+}
@@ -0,0 +1,275 @@
+//! Unified diffs between the original and the rewritten source of units
+
+/// Number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Compute a shortest edit script from `a` to `b` with Myers' algorithm.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let idx = |k: isize| (k + offset) as usize;
+    let mut v = vec![0isize; 2 * max + 3];
+    // furthest x on the diagonals `-d, -d + 2, ..., d` after each completed step `d`, which is
+    // all that the backtracking needs
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let at = |trace: &[Vec<isize>], d: isize, k: isize| trace[d as usize][((k + d) / 2) as usize];
+
+    let mut last = 0;
+    'search: for d in 0..=max as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                last = d;
+                break 'search;
+            }
+        }
+        trace.push((-d..=d).step_by(2).map(|k| v[idx(k)]).collect());
+    }
+
+    let (mut x, mut y) = (n, m);
+    let mut edits = Vec::new();
+    for d in (1..=last).rev() {
+        let k = x - y;
+        let goes_down = k == -d || (k != d && at(&trace, d - 1, k - 1) < at(&trace, d - 1, k + 1));
+        let prev_k = if goes_down { k + 1 } else { k - 1 };
+        let prev_x = at(&trace, d - 1, prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal);
+        }
+        edits.push(if x == prev_x {
+            Edit::Insert
+        } else {
+            Edit::Delete
+        });
+        (x, y) = (prev_x, prev_y);
+    }
+    // the initial snake
+    edits.extend((0..x).map(|_| Edit::Equal));
+
+    edits.reverse();
+    edits
+}
+
+/// Produce a unified diff from `old` to `new`, with 3 lines of context. Returns an empty string
+/// if both texts are identical.
+///
+/// As with GNU diff, a last line without a line terminator is followed by a
+/// `\ No newline at end of file` marker.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    // lines keep their terminator, so that adding or removing the final newline is a change
+    let a = old.split_inclusive('\n').collect::<Vec<_>>();
+    let b = new.split_inclusive('\n').collect::<Vec<_>>();
+    let edits = edit_script(&a, &b);
+
+    if edits.iter().all(|&e| e == Edit::Equal) {
+        return String::new();
+    }
+
+    // number of old and new lines consumed before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for &edit in &edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Equal => (i, j) = (i + 1, j + 1),
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let mut res = format!("--- {old_name}\n+++ {new_name}\n");
+    let mut pos = 0;
+    while let Some(first) = (pos..edits.len()).find(|&e| edits[e] != Edit::Equal) {
+        let start = first.saturating_sub(CONTEXT).max(pos);
+
+        // extend the hunk while changes are close enough to be merged
+        let mut end = first;
+        loop {
+            while end < edits.len() && edits[end] != Edit::Equal {
+                end += 1;
+            }
+            let equal_start = end;
+            while end < edits.len() && edits[end] == Edit::Equal {
+                end += 1;
+            }
+            if end == edits.len() || end - equal_start > 2 * CONTEXT {
+                end = (equal_start + CONTEXT).min(end);
+                break;
+            }
+        }
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let range = |start: usize, len: usize| {
+            // an empty range starts at the line before it, as in GNU diff
+            let start = if len == 0 { start } else { start + 1 };
+            format!("{start},{len}")
+        };
+        res.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+
+        for e in start..end {
+            let (i, j) = positions[e];
+            let (prefix, line) = match edits[e] {
+                Edit::Equal => (' ', a[i]),
+                Edit::Delete => ('-', a[i]),
+                Edit::Insert => ('+', b[j]),
+            };
+            res.push(prefix);
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    res.push_str(line);
+                    res.push('\n');
+                }
+                None => {
+                    res.push_str(line);
+                    res.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+
+        pos = end;
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        unified_diff(old, new, "a", "b")
+    }
+
+    #[test]
+    fn edit_scripts() {
+        let cases = [
+            ("abcabba", "cbabac", 5),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("abc", "abc", 0),
+            ("xaby", "ab", 2),
+            ("ab", "xaby", 2),
+        ];
+        for (a, b, distance) in cases {
+            let a = a.split("").filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let b = b.split("").filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let edits = edit_script(&a, &b);
+            let (mut i, mut j) = (0, 0);
+            let (mut old, mut new) = (Vec::new(), Vec::new());
+            for edit in &edits {
+                match edit {
+                    Edit::Equal => {
+                        assert_eq!(a[i], b[j]);
+                        old.push(a[i]);
+                        new.push(b[j]);
+                        (i, j) = (i + 1, j + 1);
+                    }
+                    Edit::Delete => {
+                        old.push(a[i]);
+                        i += 1;
+                    }
+                    Edit::Insert => {
+                        new.push(b[j]);
+                        j += 1;
+                    }
+                }
+            }
+            assert_eq!((old, new), (a.clone(), b.clone()));
+            let changes = edits.iter().filter(|&&e| e != Edit::Equal).count();
+            assert_eq!(changes, distance, "{a:?} -> {b:?}");
+        }
+    }
+
+    #[test]
+    fn identical() {
+        assert_eq!(diff("", ""), "");
+        assert_eq!(diff("a\nb\n", "a\nb\n"), "");
+        assert_eq!(diff("a\nb", "a\nb"), "");
+    }
+
+    #[test]
+    fn single_change() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nB\nc\n"),
+            "--- a\n+++ b\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+    }
+
+    #[test]
+    fn insertion_and_deletion() {
+        assert_eq!(diff("", "a\n"), "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+a\n");
+        assert_eq!(diff("a\n", ""), "--- a\n+++ b\n@@ -1,1 +0,0 @@\n-a\n");
+    }
+
+    #[test]
+    fn hunks() {
+        let lines = |f: fn(i32) -> String| (1..=20).map(f).collect::<String>();
+        let old = lines(|i| format!("{i}\n"));
+        let new = lines(|i| match i {
+            2 => "two\n".into(),
+            18 => "eighteen\n".into(),
+            i => format!("{i}\n"),
+        });
+        assert_eq!(
+            diff(&old, &new),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -15,6 +15,6 @@\n 15\n 16\n 17\n-18\n+eighteen\n 19\n 20\n"
+        );
+
+        // changes separated by at most twice the context are merged
+        let new = lines(|i| match i {
+            2 => "two\n".into(),
+            8 => "eight\n".into(),
+            i => format!("{i}\n"),
+        });
+        let diff = diff(&old, &new);
+        assert_eq!(diff.matches("@@ -").count(), 1);
+        assert!(diff.starts_with("--- a\n+++ b\n@@ -1,11 +1,11 @@\n"));
+    }
+
+    #[test]
+    fn missing_newline() {
+        assert_eq!(
+            diff("a\nb\n", "a\nb"),
+            "--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            diff("a\nb", "a\nb\n"),
+            "--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+        // unchanged last lines keep the marker in the context
+        assert_eq!(
+            diff("a\nb", "A\nb"),
+            "--- a\n+++ b\n@@ -1,2 +1,2 @@\n-a\n+A\n b\n\\ No newline at end of file\n"
+        );
+    }
+}
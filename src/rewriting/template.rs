@@ -0,0 +1,174 @@
+//! Templates for node creation
+//!
+//! Templates are Ada source fragments where each `{}` placeholder is replaced by an argument,
+//! and `{{` and `}}` are escapes for `{` and `}`. The expanded fragment is then parsed with a
+//! chosen grammar rule, see
+//! [`RewritingSession::create_from_template`](super::RewritingSession::create_from_template).
+
+use std::{error::Error, fmt::Display};
+
+use libadalang_sys::ada_node_rewriting_handle;
+
+use super::NodeRewriter;
+
+/// An argument to a template placeholder.
+#[derive(Clone, Copy)]
+pub enum TemplateArg<'s, 'a> {
+    /// An untied node, inserted in the created tree.
+    Node(NodeRewriter<'s>),
+    /// Source text, spliced into the template before parsing.
+    Text(&'a str),
+}
+
+impl<'s> From<NodeRewriter<'s>> for TemplateArg<'s, '_> {
+    fn from(value: NodeRewriter<'s>) -> Self {
+        Self::Node(value)
+    }
+}
+
+impl<'a> From<&'a str> for TemplateArg<'_, 'a> {
+    fn from(value: &'a str) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl<'a> From<&'a String> for TemplateArg<'_, 'a> {
+    fn from(value: &'a String) -> Self {
+        Self::Text(value)
+    }
+}
+
+/// Error raised when the template placeholders do not match the arguments.
+#[derive(Debug, Clone)]
+pub struct TemplateError {
+    pub message: String,
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid template: {}", self.message)
+    }
+}
+
+impl Error for TemplateError {}
+
+impl From<TemplateError> for crate::Error {
+    fn from(value: TemplateError) -> Self {
+        Self::Template(value)
+    }
+}
+
+/// Splice the text arguments into `template`, returning the template that remains to be passed
+/// to libadalang and its node arguments.
+pub(super) fn expand(
+    template: &str,
+    arguments: &[TemplateArg<'_, '_>],
+) -> Result<(String, Vec<ada_node_rewriting_handle>), TemplateError> {
+    let mut res = String::with_capacity(template.len());
+    let mut nodes = Vec::new();
+    let mut args = arguments.iter();
+    let mut placeholders = 0;
+
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                res.push(c);
+                res.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                match args.next() {
+                    Some(TemplateArg::Node(node)) => {
+                        res.push_str("{}");
+                        nodes.push(node.raw);
+                    }
+                    Some(TemplateArg::Text(text)) => {
+                        res.push_str(&text.replace('{', "{{").replace('}', "}}"));
+                    }
+                    None => {}
+                }
+            }
+            ('{' | '}', _) => {
+                return Err(TemplateError {
+                    message: format!("unescaped `{c}`, use `{c}{c}` instead"),
+                });
+            }
+            (c, _) => res.push(c),
+        }
+    }
+
+    if placeholders != arguments.len() {
+        return Err(TemplateError {
+            message: format!(
+                "expected {placeholders} argument(s), found {}",
+                arguments.len()
+            ),
+        });
+    }
+
+    Ok((res, nodes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{marker::PhantomData, ptr::NonNull};
+
+    use super::*;
+
+    fn node(raw: ada_node_rewriting_handle) -> TemplateArg<'static, 'static> {
+        TemplateArg::Node(NodeRewriter {
+            raw,
+            _session: PhantomData,
+        })
+    }
+
+    fn message(template: &str, arguments: &[TemplateArg<'_, '_>]) -> String {
+        match expand(template, arguments) {
+            Err(err) => err.message,
+            Ok((res, _)) => panic!("`{template}` should not expand, got `{res}`"),
+        }
+    }
+
+    #[test]
+    fn expand_text() {
+        let (res, nodes) = expand("X := {} + {};", &["Y".into(), "1".into()]).unwrap();
+        assert_eq!(res, "X := Y + 1;");
+        assert!(nodes.is_empty());
+    }
+
+    #[test]
+    fn expand_nodes() {
+        let a = NonNull::dangling().as_ptr();
+        let b = NonNull::<u64>::dangling().as_ptr().cast();
+        let (res, nodes) = expand("{} := {} + {};", &[node(a), "1".into(), node(b)]).unwrap();
+        assert_eq!(res, "{} := 1 + {};");
+        assert_eq!(nodes, [a, b]);
+    }
+
+    #[test]
+    fn expand_escapes() {
+        // escapes are kept for libadalang
+        let (res, _) = expand("{{}} {{{}}}", &["A".into()]).unwrap();
+        assert_eq!(res, "{{}} {{A}}");
+        // braces in text arguments are escaped for libadalang
+        let (res, _) = expand("S := {};", &[r#""{}""#.into()]).unwrap();
+        assert_eq!(res, r#"S := "{{}}";"#);
+    }
+
+    #[test]
+    fn expand_errors() {
+        assert_eq!(
+            message("{} {}", &["A".into()]),
+            "expected 2 argument(s), found 1"
+        );
+        assert_eq!(
+            message("X", &["A".into()]),
+            "expected 0 argument(s), found 1"
+        );
+        assert_eq!(message("X := {;", &[]), "unescaped `{`, use `{{` instead");
+        assert_eq!(message("} X", &[]), "unescaped `}`, use `}}` instead");
+    }
+}
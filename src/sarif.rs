@@ -0,0 +1,504 @@
+//! SARIF output for diagnostics and checker results
+//!
+//! A [`SarifLog`] collects [`Finding`]s, e.g. the diagnostics of many units or the results of a
+//! custom checker, and converts them into a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html)
+//! log with a single run.
+//!
+//! SARIF columns count Unicode code points, while libadalang expands tabs with the tab stop of
+//! the context: [`SarifLog::add_unit`] converts the columns of diagnostics with the source of
+//! their unit, and [`code_point_range`] does so for other findings.
+
+use std::num::NonZeroU8;
+
+use serde::Serialize;
+
+use crate::{
+    analysis::Unit,
+    diagnostic::{Diagnostic, Severity, SourceLocation, SourceRange, unit_source},
+};
+
+/// Rule id of the findings created from libadalang diagnostics.
+pub const DIAGNOSTIC_RULE_ID: &str = "libadalang-diagnostic";
+
+/// Severity of a finding, as a SARIF level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    None,
+    Note,
+    #[default]
+    Warning,
+    Error,
+}
+
+/// A result to report, attached to a range of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule_id: String,
+    pub level: Level,
+    pub message: String,
+    /// Name of the source file, or an empty string if unknown
+    pub filename: String,
+    /// Range of the finding, with columns counting code points
+    pub sloc_range: SourceRange,
+}
+
+impl Finding {
+    pub fn new(
+        rule_id: impl Into<String>,
+        level: Level,
+        message: impl Into<String>,
+        filename: impl Into<String>,
+        sloc_range: SourceRange,
+    ) -> Self {
+        Self {
+            rule_id: rule_id.into(),
+            level,
+            message: message.into(),
+            filename: filename.into(),
+            sloc_range,
+        }
+    }
+
+    /// Create a finding from a diagnostic, with the [`DIAGNOSTIC_RULE_ID`] rule.
+    ///
+    /// The columns of the diagnostic are kept, which is only right for a context with a tab stop
+    /// of 1 or for lines without tabs, see [`code_point_range`].
+    pub fn from_diagnostic(diag: &Diagnostic) -> Self {
+        Self::new(
+            DIAGNOSTIC_RULE_ID,
+            match diag.severity {
+                Severity::Warning => Level::Warning,
+                Severity::Error => Level::Error,
+            },
+            &diag.message,
+            diag.filename.as_deref().unwrap_or_default(),
+            diag.sloc_range,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    id: String,
+    description: Option<String>,
+}
+
+/// A SARIF log, built from findings.
+#[derive(Debug, Clone)]
+pub struct SarifLog {
+    tool_name: String,
+    tool_version: Option<String>,
+    information_uri: Option<String>,
+    rules: Vec<Rule>,
+    findings: Vec<Finding>,
+}
+
+impl SarifLog {
+    /// Create an empty log for the tool named `tool_name`.
+    pub fn new(tool_name: impl Into<String>) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            tool_version: None,
+            information_uri: None,
+            rules: Vec::new(),
+            findings: Vec::new(),
+        }
+    }
+
+    /// Set the version of the tool.
+    pub fn tool_version(mut self, version: impl Into<String>) -> Self {
+        self.tool_version = Some(version.into());
+        self
+    }
+
+    /// Set the URI of the documentation of the tool.
+    pub fn information_uri(mut self, uri: impl Into<String>) -> Self {
+        self.information_uri = Some(uri.into());
+        self
+    }
+
+    /// Declare a rule with its description.
+    ///
+    /// Rules used by findings are declared automatically, without description.
+    pub fn rule(mut self, id: impl Into<String>, description: impl Into<String>) -> Self {
+        let id = id.into();
+        let description = Some(description.into());
+        match self.rules.iter_mut().find(|rule| rule.id == id) {
+            Some(rule) => rule.description = description,
+            None => self.rules.push(Rule { id, description }),
+        }
+        self
+    }
+
+    /// Add a finding to the log.
+    pub fn add_finding(&mut self, finding: Finding) {
+        if !self.rules.iter().any(|rule| rule.id == finding.rule_id) {
+            self.rules.push(Rule {
+                id: finding.rule_id.clone(),
+                description: None,
+            });
+        }
+        self.findings.push(finding);
+    }
+
+    /// Add a diagnostic to the log, keeping its columns, see [`Finding::from_diagnostic`].
+    pub fn add_diagnostic(&mut self, diag: &Diagnostic) {
+        self.add_finding(Finding::from_diagnostic(diag));
+    }
+
+    /// Add all the diagnostics of `unit` to the log, converting their columns to code points.
+    pub fn add_unit(&mut self, unit: &Unit) {
+        let source = unit_source(unit);
+        let tab_stop = unit.tab_stop();
+        for diag in unit.diagnostics() {
+            let mut finding = Finding::from_diagnostic(&diag);
+            finding.sloc_range = code_point_range(&source, tab_stop, finding.sloc_range);
+            self.add_finding(finding);
+        }
+    }
+
+    /// Findings added to the log so far.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Serialize the log to a JSON string.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("SARIF logs are always serializable")
+    }
+}
+
+impl Extend<Finding> for SarifLog {
+    fn extend<T: IntoIterator<Item = Finding>>(&mut self, iter: T) {
+        for finding in iter {
+            self.add_finding(finding);
+        }
+    }
+}
+
+/// Convert a range of `source` with libadalang columns, where tabs are expanded with
+/// `tab_stop`, to a range with columns counting code points.
+pub fn code_point_range(source: &str, tab_stop: NonZeroU8, range: SourceRange) -> SourceRange {
+    let convert = |loc: SourceLocation| {
+        let Some(line) = source.lines().nth((loc.line as usize).saturating_sub(1)) else {
+            return loc;
+        };
+        let tab_stop = u32::from(tab_stop.get());
+        let target = u32::from(loc.column);
+        // expanded and code point columns of the current character
+        let (mut column, mut code_points) = (1u32, 1u32);
+        for c in line.chars() {
+            if column >= target {
+                break;
+            }
+            column += if c == '\t' {
+                tab_stop - (column - 1) % tab_stop
+            } else {
+                1
+            };
+            code_points += 1;
+        }
+        // past the end of the line, columns map one to one
+        let code_points = code_points + target.saturating_sub(column);
+        SourceLocation::new(loc.line, u16::try_from(code_points).unwrap_or(u16::MAX))
+    };
+    SourceRange::new(convert(range.start), convert(range.end))
+}
+
+/// Convert a filename to a URI reference, as expected in artifact locations.
+fn file_uri(filename: &str) -> String {
+    let path = filename.replace('\\', "/");
+    // absolute Windows path, e.g. C:/src/main.adb, whose drive is kept as is
+    let drive = match path.as_bytes() {
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => 2,
+        _ => 0,
+    };
+
+    let mut res = String::with_capacity(path.len());
+    res.push_str(&path[..drive]);
+    for byte in path[drive..].bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            res.push(char::from(byte));
+        } else {
+            res.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    if res.starts_with('/') {
+        format!("file://{res}")
+    } else if drive != 0 {
+        format!("file:///{res}")
+    } else {
+        res
+    }
+}
+
+// The structures below follow the SARIF 2.1.0 JSON schema.
+
+#[derive(Serialize)]
+struct Log<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [Run<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run<'a> {
+    tool: Tool<'a>,
+    column_kind: &'static str,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct Tool<'a> {
+    driver: Driver<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    information_uri: Option<&'a str>,
+    rules: Vec<ReportingDescriptor<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportingDescriptor<'a> {
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_description: Option<Message<'a>>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'a str,
+    rule_index: usize,
+    level: Level,
+    message: Message<'a>,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u32,
+    start_column: u16,
+    end_line: u32,
+    end_column: u16,
+}
+
+impl Serialize for SarifLog {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| ReportingDescriptor {
+                id: &rule.id,
+                short_description: rule.description.as_deref().map(|text| Message { text }),
+            })
+            .collect();
+
+        let results = self
+            .findings
+            .iter()
+            .map(|finding| {
+                let SourceRange { start, end } = finding.sloc_range;
+                SarifResult {
+                    rule_id: &finding.rule_id,
+                    rule_index: self
+                        .rules
+                        .iter()
+                        .position(|rule| rule.id == finding.rule_id)
+                        .unwrap_or_default(),
+                    level: finding.level,
+                    message: Message {
+                        text: &finding.message,
+                    },
+                    // a physical location needs an artifact, so findings without a file have
+                    // no location
+                    locations: (!finding.filename.is_empty())
+                        .then(|| Location {
+                            physical_location: PhysicalLocation {
+                                artifact_location: ArtifactLocation {
+                                    uri: file_uri(&finding.filename),
+                                },
+                                // SARIF lines and columns start at 1
+                                region: Region {
+                                    start_line: start.line.max(1),
+                                    start_column: start.column.max(1),
+                                    end_line: end.line.max(1),
+                                    end_column: end.column.max(1),
+                                },
+                            },
+                        })
+                        .into_iter()
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Log {
+            schema: "https://json.schemastore.org/sarif-2.1.0.json",
+            version: "2.1.0",
+            runs: [Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: &self.tool_name,
+                        version: self.tool_version.as_deref(),
+                        information_uri: self.information_uri.as_deref(),
+                        rules,
+                    },
+                },
+                column_kind: "unicodeCodePoints",
+                results,
+            }],
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn range(start: (u32, u16), end: (u32, u16)) -> SourceRange {
+        SourceRange::new(
+            SourceLocation::new(start.0, start.1),
+            SourceLocation::new(end.0, end.1),
+        )
+    }
+
+    #[test]
+    fn code_points() {
+        let tab_stop = NonZeroU8::new(3).unwrap();
+        let source = "procedure P is\n\tX : Integer;\na\tbé\tc\n";
+        // `X` is at column 4, after a tab expanded to 3 columns
+        assert_eq!(
+            code_point_range(source, tab_stop, range((2, 4), (2, 5))),
+            range((2, 2), (2, 3))
+        );
+        // `a` is at column 1, `b` at 4 and `c` at 7
+        assert_eq!(
+            code_point_range(source, tab_stop, range((3, 4), (3, 8))),
+            range((3, 3), (3, 7))
+        );
+        // lines without tabs, past the end of a line, or past the end of the source are kept
+        assert_eq!(
+            code_point_range(source, tab_stop, range((1, 11), (2, 17))),
+            range((1, 11), (2, 15))
+        );
+        assert_eq!(
+            code_point_range(source, tab_stop, range((9, 2), (9, 3))),
+            range((9, 2), (9, 3))
+        );
+        assert_eq!(
+            code_point_range(source, NonZeroU8::MIN, range((2, 2), (2, 3))),
+            range((2, 2), (2, 3))
+        );
+    }
+
+    #[test]
+    fn uris() {
+        assert_eq!(file_uri("/src/main.adb"), "file:///src/main.adb");
+        assert_eq!(file_uri("src/my file.adb"), "src/my%20file.adb");
+        assert_eq!(
+            file_uri("/src/a#b?c%d.adb"),
+            "file:///src/a%23b%3Fc%25d.adb"
+        );
+        assert_eq!(file_uri("/src/é.adb"), "file:///src/%C3%A9.adb");
+        assert_eq!(file_uri("C:\\src\\main.adb"), "file:///C:/src/main.adb");
+    }
+
+    #[test]
+    fn serialize() {
+        let mut log = SarifLog::new("checker")
+            .tool_version("1.0")
+            .rule("no-goto", "Do not use goto");
+        log.add_finding(Finding::new(
+            "no-goto",
+            Level::Error,
+            "goto statement",
+            "/src/main.adb",
+            range((3, 4), (3, 12)),
+        ));
+        log.add_diagnostic(&Diagnostic::new(range((0, 0), (0, 0)), "cannot read file"));
+
+        let log = serde_json::from_str::<Value>(&log.to_json()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        assert_eq!(
+            run["tool"]["driver"],
+            json!({
+                "name": "checker",
+                "version": "1.0",
+                "rules": [
+                    { "id": "no-goto", "shortDescription": { "text": "Do not use goto" } },
+                    { "id": DIAGNOSTIC_RULE_ID },
+                ],
+            })
+        );
+        assert_eq!(
+            run["results"],
+            json!([
+                {
+                    "ruleId": "no-goto",
+                    "ruleIndex": 0,
+                    "level": "error",
+                    "message": { "text": "goto statement" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "file:///src/main.adb" },
+                            "region": {
+                                "startLine": 3,
+                                "startColumn": 4,
+                                "endLine": 3,
+                                "endColumn": 12,
+                            },
+                        },
+                    }],
+                },
+                {
+                    "ruleId": DIAGNOSTIC_RULE_ID,
+                    "ruleIndex": 1,
+                    "level": "error",
+                    "message": { "text": "cannot read file" },
+                    "locations": [],
+                },
+            ])
+        );
+    }
+}
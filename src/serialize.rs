@@ -0,0 +1,487 @@
+//! Serialization of syntax trees with [`serde`]
+//!
+//! [`Node`] and [`Unit`] implement [`Serialize`], so they can be converted to JSON with e.g.
+//! `serde_json::to_string(&unit)`. Use [`Node::serialize_with`] and [`Unit::serialize_with`] to
+//! pass [`SerializeOptions`].
+//!
+//! Each node of the subtree is serialized as a map with the following entries:
+//! - `kind`: the name of the node kind, e.g. `CallExpr`
+//! - `sloc_range`: `{ "start": { "line": 1, "column": 1 }, "end": { ... } }`
+//! - `text`: the text of token nodes
+//! - `fields`: the syntax fields of regular nodes, by name, with `null` for absent nodes
+//! - `children`: the children of the other nodes, e.g. lists
+//! - `properties`: the values of the properties that can be evaluated without arguments, when
+//!   enabled. Nodes are only referenced there, by `kind` and `sloc_range`.
+//!
+//! A unit is serialized as a map with its `filename` and its `root` node. With trivia enabled,
+//! the unit, or the root of a serialized subtree, also has a `trivia` entry listing the trivia
+//! tokens (comments, whitespace...) it contains, with their `kind`, `text` and `sloc_range`.
+
+use std::{
+    ffi::{CStr, c_void},
+    mem::MaybeUninit,
+};
+
+use libadalang_sys::{
+    ada_free, ada_node_token_end, ada_node_token_start, ada_token, ada_token_get_kind,
+    ada_token_kind_name, ada_token_next, ada_token_range_text, ada_token_sloc_range,
+    ada_unit_first_token, ada_unit_last_token,
+};
+use serde::{
+    Serialize, Serializer,
+    ser::{Error as _, SerializeMap, SerializeSeq},
+};
+
+use crate::{
+    analysis::{Node, Unit},
+    big_int::BigInt,
+    diagnostic::SourceRange,
+    introspection::{MemberInfo, MemberKind, Value, param_default},
+    structs::{
+        CompletionItem, DiscriminantValues, DocAnnotation, ParamActual, ParamMatch, RefResult,
+        Shape, Substitution,
+    },
+    text,
+};
+
+/// Options of the serialization of syntax trees.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerializeOptions {
+    trivia: bool,
+    properties: bool,
+}
+
+impl SerializeOptions {
+    /// Create options that only serialize the syntax tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to serialize trivia. The context must have been created with trivia.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// Whether to serialize the properties of each node.
+    ///
+    /// This evaluates every property that does not need arguments, which is slow on large trees.
+    pub fn with_properties(mut self, properties: bool) -> Self {
+        self.properties = properties;
+        self
+    }
+}
+
+/// A node subtree, serialized with options.
+pub struct SerializeNode<'a> {
+    node: &'a Node,
+    options: SerializeOptions,
+    is_root: bool,
+}
+
+/// A unit, serialized with options.
+pub struct SerializeUnit<'a> {
+    unit: &'a Unit,
+    options: SerializeOptions,
+}
+
+impl Node {
+    /// Serialize the subtree rooted at this node with the given options.
+    pub fn serialize_with(&self, options: SerializeOptions) -> SerializeNode<'_> {
+        SerializeNode {
+            node: self,
+            options,
+            is_root: true,
+        }
+    }
+}
+
+impl Unit {
+    /// Serialize this unit with the given options.
+    pub fn serialize_with(&self, options: SerializeOptions) -> SerializeUnit<'_> {
+        SerializeUnit {
+            unit: self,
+            options,
+        }
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with(SerializeOptions::new())
+            .serialize(serializer)
+    }
+}
+
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with(SerializeOptions::new())
+            .serialize(serializer)
+    }
+}
+
+/// A reference to a node, as the result of a property.
+struct NodeRef<'a>(Option<&'a Node>);
+
+impl Serialize for NodeRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(node) = self.0 else {
+            return serializer.serialize_none();
+        };
+        let sloc_range = node.sloc_range().map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("kind", &node.kind_name())?;
+        map.serialize_entry("sloc_range", &sloc_range)?;
+        map.end()
+    }
+}
+
+fn node_ref(node: &Option<Node>) -> NodeRef<'_> {
+    NodeRef(node.as_ref())
+}
+
+/// A trivia token.
+struct Trivia {
+    kind: String,
+    text: String,
+    sloc_range: SourceRange,
+}
+
+impl Serialize for Trivia {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("kind", &self.kind)?;
+        map.serialize_entry("text", &self.text)?;
+        map.serialize_entry("sloc_range", &self.sloc_range)?;
+        map.end()
+    }
+}
+
+/// Collect the trivia tokens from `first` to `last`, included.
+fn trivia(first: ada_token, last: ada_token) -> Vec<Trivia> {
+    let mut res = Vec::new();
+    let mut token = first;
+    loop {
+        if token.trivia_index != 0 {
+            let mut text = text::empty_raw();
+            let mut sloc_range = MaybeUninit::uninit();
+            let kind = unsafe {
+                ada_token_range_text(&raw mut token, &raw mut token, &raw mut text);
+                ada_token_sloc_range(&raw mut token, sloc_range.as_mut_ptr());
+                let name = ada_token_kind_name(ada_token_get_kind(&raw mut token));
+                let kind = CStr::from_ptr(name).to_string_lossy().into_owned();
+                ada_free(name.cast::<c_void>());
+                kind
+            };
+            res.push(Trivia {
+                kind,
+                text: text::take_string(text),
+                sloc_range: SourceRange::from_raw(unsafe { sloc_range.assume_init() }),
+            });
+        }
+
+        let is_last =
+            token.token_index == last.token_index && token.trivia_index == last.trivia_index;
+        let mut next = MaybeUninit::<ada_token>::uninit();
+        if is_last || unsafe { ada_token_next(&raw mut token, next.as_mut_ptr()) } == 0 {
+            break;
+        }
+        token = unsafe { next.assume_init() };
+        if token.token_data.is_null() {
+            break;
+        }
+    }
+
+    crate::exception::Exception::log_and_ignore();
+    res
+}
+
+fn node_trivia(node: &Node) -> crate::Result<Vec<Trivia>> {
+    let node = node.as_ptr()?;
+    let mut first = MaybeUninit::uninit();
+    let mut last = MaybeUninit::uninit();
+    unsafe {
+        ada_node_token_start(node, first.as_mut_ptr());
+        ada_node_token_end(node, last.as_mut_ptr());
+        Ok(trivia(first.assume_init(), last.assume_init()))
+    }
+}
+
+fn unit_trivia(unit: &Unit) -> Vec<Trivia> {
+    let mut first = MaybeUninit::uninit();
+    let mut last = MaybeUninit::uninit();
+    unsafe {
+        ada_unit_first_token(unit.as_raw(), first.as_mut_ptr());
+        ada_unit_last_token(unit.as_raw(), last.as_mut_ptr());
+        trivia(first.assume_init(), last.assume_init())
+    }
+}
+
+/// Whether the property `member` can be evaluated without arguments.
+fn is_serializable_property(member: &MemberInfo) -> bool {
+    member.kind == MemberKind::Property
+        && member.is_supported()
+        && member
+            .params
+            .iter()
+            .all(|param| param_default(param).is_some())
+}
+
+impl Serialize for SerializeNode<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node = self.node;
+        let sloc_range = node.sloc_range().map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", &node.kind_name())?;
+        map.serialize_entry("sloc_range", &sloc_range)?;
+
+        let fields = node
+            .members()
+            .filter(|member| member.kind == MemberKind::Field)
+            .collect::<Vec<_>>();
+        if node.is_token_node().map_err(S::Error::custom)? {
+            map.serialize_entry("text", &node.text().map_err(S::Error::custom)?)?;
+        } else if fields.is_empty() {
+            let children = node
+                .children()
+                .map_err(S::Error::custom)?
+                .collect::<Vec<_>>();
+            let children = children
+                .iter()
+                .map(|node| SerializeNode {
+                    node,
+                    options: self.options,
+                    is_root: false,
+                })
+                .collect::<Vec<_>>();
+            map.serialize_entry("children", &children)?;
+        } else {
+            map.serialize_entry(
+                "fields",
+                &Fields {
+                    node,
+                    fields: &fields,
+                    options: self.options,
+                },
+            )?;
+        }
+
+        if self.options.properties {
+            map.serialize_entry("properties", &Properties(node))?;
+        }
+        if self.options.trivia && self.is_root {
+            let trivia = node_trivia(node).map_err(S::Error::custom)?;
+            map.serialize_entry("trivia", &trivia)?;
+        }
+
+        map.end()
+    }
+}
+
+struct Fields<'a> {
+    node: &'a Node,
+    fields: &'a [MemberInfo],
+    options: SerializeOptions,
+}
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in self.fields {
+            let value = self
+                .node
+                .field(field.name)
+                .map_err(S::Error::custom)?
+                .flatten();
+            map.serialize_entry(
+                field.name,
+                &value.as_ref().map(|node| SerializeNode {
+                    node,
+                    options: self.options,
+                    is_root: false,
+                }),
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct Properties<'a>(&'a Node);
+
+impl Serialize for Properties<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for member in self.0.members().filter(is_serializable_property) {
+            // properties raising an exception are skipped
+            if let Ok(value) = self.0.eval(member.name, &[]) {
+                map.serialize_entry(member.name, &value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl Serialize for SerializeUnit<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let root = self.unit.root();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("filename", &self.unit.filename())?;
+        map.serialize_entry(
+            "root",
+            &root.as_ref().map(|node| SerializeNode {
+                node,
+                options: self.options,
+                is_root: false,
+            }),
+        )?;
+        if self.options.trivia {
+            map.serialize_entry("trivia", &unit_trivia(self.unit))?;
+        }
+        map.end()
+    }
+}
+
+/// A sequence of nodes, possibly null.
+struct NodeRefs<'a, T>(&'a [T]);
+
+impl<T> Serialize for NodeRefs<'_, T>
+where
+    for<'b> &'b T: Into<Option<&'b Node>>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for node in self.0 {
+            seq.serialize_element(&NodeRef(node.into()))?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for ParamActual {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("param", &node_ref(&self.param))?;
+        map.serialize_entry("actual", &node_ref(&self.actual))?;
+        map.end()
+    }
+}
+
+impl Serialize for DiscriminantValues {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("discriminant", &node_ref(&self.discriminant))?;
+        map.serialize_entry("values", &node_ref(&self.values))?;
+        map.end()
+    }
+}
+
+impl Serialize for Shape {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("components", &NodeRefs(&self.components))?;
+        map.serialize_entry("discriminants_values", &self.discriminants_values)?;
+        map.end()
+    }
+}
+
+impl Serialize for RefResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("ref", &node_ref(&self.reference))?;
+        map.serialize_entry("kind", &format!("{:?}", self.kind))?;
+        map.end()
+    }
+}
+
+impl Serialize for ParamMatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("has_matched", &self.has_matched)?;
+        map.serialize_entry("actual", &node_ref(&self.actual))?;
+        map.serialize_entry("formal", &node_ref(&self.formal))?;
+        map.end()
+    }
+}
+
+impl Serialize for CompletionItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry("decl", &node_ref(&self.decl))?;
+        map.serialize_entry("is_dot_call", &self.is_dot_call)?;
+        map.serialize_entry("is_visible", &self.is_visible)?;
+        map.serialize_entry("weight", &self.weight)?;
+        map.end()
+    }
+}
+
+impl Serialize for DocAnnotation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("key", &self.key)?;
+        map.serialize_entry("value", &self.value)?;
+        map.end()
+    }
+}
+
+impl Serialize for Substitution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("from_decl", &node_ref(&self.from_decl))?;
+        map.serialize_entry("to_value", &self.to_value.as_ref().map(BigInt::to_decimal))?;
+        map.serialize_entry("value_type", &node_ref(&self.value_type))?;
+        map.end()
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Int(i) => serializer.serialize_i32(*i),
+            Value::Node(node) => node_ref(node).serialize(serializer),
+            Value::Symbol(symbol) => serializer.serialize_str(symbol.as_str()),
+            Value::String(string) => serializer.serialize_str(string),
+            // as a string, since it may not fit in the integers of the target format
+            Value::BigInt(big_int) => serializer.serialize_str(&big_int.to_decimal()),
+            Value::Unit(unit) => unit.as_ref().map(Unit::filename).serialize(serializer),
+            Value::Nodes(nodes) => NodeRefs(nodes).serialize(serializer),
+            Value::Units(units) => {
+                serializer.collect_seq(units.iter().map(|unit| unit.as_ref().map(Unit::filename)))
+            }
+            Value::Symbols(symbols) => serializer.collect_seq(symbols.iter().map(|s| s.as_str())),
+            Value::RefdDecl(refd) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("decl", &node_ref(&refd.decl))?;
+                map.serialize_entry("kind", &format!("{:?}", refd.kind))?;
+                map.end()
+            }
+            Value::RefdDef(refd) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("def_name", &node_ref(&refd.def_name))?;
+                map.serialize_entry("kind", &format!("{:?}", refd.kind))?;
+                map.end()
+            }
+            Value::Aspect(aspect) => {
+                let mut map = serializer.serialize_map(Some(4))?;
+                map.serialize_entry("exists", &aspect.exists)?;
+                map.serialize_entry("node", &node_ref(&aspect.node))?;
+                map.serialize_entry("value", &node_ref(&aspect.value))?;
+                map.serialize_entry("inherited", &aspect.inherited)?;
+                map.end()
+            }
+            Value::DiscreteRange(range) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("low_bound", &node_ref(&range.low_bound))?;
+                map.serialize_entry("high_bound", &node_ref(&range.high_bound))?;
+                map.end()
+            }
+            Value::ParamActuals(actuals) => actuals.serialize(serializer),
+            Value::Shapes(shapes) => shapes.serialize(serializer),
+            Value::RefResults(results) => results.serialize(serializer),
+            Value::ParamMatches(matches) => matches.serialize(serializer),
+            Value::CompletionItems(items) => items.serialize(serializer),
+            Value::DocAnnotations(annotations) => annotations.serialize(serializer),
+            Value::Substitutions(substitutions) => substitutions.serialize(serializer),
+        }
+    }
+}
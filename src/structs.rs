@@ -0,0 +1,336 @@
+//! Owned Rust counterparts of the structures returned by properties
+//!
+//! The raw `ada_internal_*` structures are converted with their `from_raw` functions, which take
+//! ownership of the raw value and release its reference-counted fields.
+
+use std::mem::ManuallyDrop;
+
+use libadalang_sys::{
+    ada_base_entity, ada_internal_aspect, ada_internal_completion_item,
+    ada_internal_discrete_range, ada_internal_discriminant_values, ada_internal_doc_annotation,
+    ada_internal_doc_annotation_dec_ref, ada_internal_param_actual, ada_internal_param_match,
+    ada_internal_ref_result, ada_internal_refd_decl, ada_internal_refd_def, ada_internal_shape,
+    ada_internal_shape_dec_ref, ada_internal_substitution, ada_internal_substitution_dec_ref,
+    ada_ref_result_kind,
+};
+
+use crate::{
+    analysis::Node,
+    array::{FromRawItem, RawArray},
+    big_int::BigInt,
+    exception::Exception,
+};
+
+/// Kind for the result of a cross reference operation.
+pub type RefResultKind = ada_ref_result_kind;
+
+fn node(raw: ada_base_entity) -> Option<Node> {
+    unsafe { Node::from_raw(raw) }
+}
+
+/// Result of a name resolution to a node.
+#[derive(Clone, Copy)]
+pub struct RefResult {
+    /// The referenced node, if any
+    pub reference: Option<Node>,
+    pub kind: RefResultKind,
+}
+
+impl RefResult {
+    pub fn from_raw(raw: ada_internal_ref_result) -> Self {
+        Self {
+            reference: node(raw.ref_),
+            kind: raw.kind,
+        }
+    }
+}
+
+/// Result of a name resolution to a declaration.
+#[derive(Clone, Copy)]
+pub struct RefdDecl {
+    pub decl: Option<Node>,
+    pub kind: RefResultKind,
+}
+
+impl RefdDecl {
+    pub fn from_raw(raw: ada_internal_refd_decl) -> Self {
+        Self {
+            decl: node(raw.decl),
+            kind: raw.kind,
+        }
+    }
+}
+
+/// Result of a name resolution to a defining name.
+#[derive(Clone, Copy)]
+pub struct RefdDef {
+    pub def_name: Option<Node>,
+    pub kind: RefResultKind,
+}
+
+impl RefdDef {
+    pub fn from_raw(raw: ada_internal_refd_def) -> Self {
+        Self {
+            def_name: node(raw.def_name),
+            kind: raw.kind,
+        }
+    }
+}
+
+/// An aspect (or the equivalent pragma or representation clause) of an entity.
+#[derive(Clone, Copy)]
+pub struct Aspect {
+    /// Whether the aspect is defined or not
+    pub exists: bool,
+    /// Syntactic node that defines the aspect
+    pub node: Option<Node>,
+    /// Expression associated to the aspect
+    pub value: Option<Node>,
+    /// Whether the aspect is inherited (it has been defined by a parent)
+    pub inherited: bool,
+}
+
+impl Aspect {
+    pub fn from_raw(raw: ada_internal_aspect) -> Self {
+        Self {
+            exists: raw.exists != 0,
+            node: node(raw.node),
+            value: node(raw.value),
+            inherited: raw.inherited != 0,
+        }
+    }
+}
+
+/// Association of a formal parameter with its actual in a call.
+#[derive(Clone, Copy)]
+pub struct ParamActual {
+    pub param: Option<Node>,
+    pub actual: Option<Node>,
+}
+
+impl ParamActual {
+    pub fn from_raw(raw: ada_internal_param_actual) -> Self {
+        Self {
+            param: node(raw.param),
+            actual: node(raw.actual),
+        }
+    }
+}
+
+/// Result of matching an actual parameter with a formal one.
+#[derive(Clone, Copy)]
+pub struct ParamMatch {
+    /// Whether the matched formal has an actual
+    pub has_matched: bool,
+    pub actual: Option<Node>,
+    pub formal: Option<Node>,
+}
+
+impl ParamMatch {
+    pub fn from_raw(raw: ada_internal_param_match) -> Self {
+        Self {
+            has_matched: raw.has_matched != 0,
+            actual: node(raw.actual),
+            formal: node(raw.formal),
+        }
+    }
+}
+
+/// Bounds of a discrete range. Both bounds are `None` for an unbounded range.
+#[derive(Clone, Copy)]
+pub struct DiscreteRange {
+    pub low_bound: Option<Node>,
+    pub high_bound: Option<Node>,
+}
+
+impl DiscreteRange {
+    pub fn from_raw(raw: ada_internal_discrete_range) -> Self {
+        Self {
+            low_bound: node(raw.low_bound),
+            high_bound: node(raw.high_bound),
+        }
+    }
+}
+
+/// A completion candidate.
+#[derive(Clone, Copy)]
+pub struct CompletionItem {
+    pub decl: Option<Node>,
+    pub is_dot_call: bool,
+    pub is_visible: bool,
+    pub weight: i32,
+}
+
+impl CompletionItem {
+    pub fn from_raw(raw: ada_internal_completion_item) -> Self {
+        Self {
+            decl: node(raw.decl),
+            is_dot_call: raw.is_dot_call != 0,
+            is_visible: raw.is_visible != 0,
+            weight: raw.weight,
+        }
+    }
+}
+
+/// The values of a discriminant in a record variant.
+#[derive(Clone, Copy)]
+pub struct DiscriminantValues {
+    pub discriminant: Option<Node>,
+    pub values: Option<Node>,
+}
+
+impl DiscriminantValues {
+    pub fn from_raw(raw: &ada_internal_discriminant_values) -> Self {
+        Self {
+            discriminant: node(raw.discriminant),
+            values: node(raw.values),
+        }
+    }
+}
+
+/// A possible shape of a record type: its components, and the discriminant values that select
+/// them.
+#[derive(Clone)]
+pub struct Shape {
+    pub components: Vec<Option<Node>>,
+    pub discriminants_values: Vec<DiscriminantValues>,
+}
+
+impl Shape {
+    /// Convert a raw shape, releasing its arrays.
+    pub fn from_raw(mut raw: ada_internal_shape) -> Self {
+        let res = Self::copy_from_raw(&raw);
+        unsafe { ada_internal_shape_dec_ref(&raw mut raw) };
+        Exception::log_and_ignore();
+        res
+    }
+
+    /// Convert a raw shape without releasing it.
+    fn copy_from_raw(raw: &ada_internal_shape) -> Self {
+        Self {
+            components: if raw.components.is_null() {
+                Vec::new()
+            } else {
+                unsafe { raw.components.items() }
+                    .iter()
+                    .map(|&entity| node(entity))
+                    .collect()
+            },
+            discriminants_values: if raw.discriminants_values.is_null() {
+                Vec::new()
+            } else {
+                unsafe { raw.discriminants_values.items() }
+                    .iter()
+                    .map(DiscriminantValues::from_raw)
+                    .collect()
+            },
+        }
+    }
+}
+
+/// A documentation annotation, e.g. `--% key: value` in a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocAnnotation {
+    pub key: String,
+    pub value: String,
+}
+
+impl DocAnnotation {
+    /// Convert a raw annotation, releasing its strings.
+    pub fn from_raw(mut raw: ada_internal_doc_annotation) -> Self {
+        let res = Self::copy_from_raw(&raw);
+        unsafe { ada_internal_doc_annotation_dec_ref(&raw mut raw) };
+        Exception::log_and_ignore();
+        res
+    }
+
+    /// Convert a raw annotation without releasing it.
+    fn copy_from_raw(raw: &ada_internal_doc_annotation) -> Self {
+        unsafe {
+            Self {
+                key: String::from_raw_item(&raw.key),
+                value: String::from_raw_item(&raw.value),
+            }
+        }
+    }
+}
+
+/// The substitution of a declaration by a static value, e.g. a formal object of a generic by
+/// the value of its actual.
+#[derive(Clone)]
+pub struct Substitution {
+    /// The declaration to substitute
+    pub from_decl: Option<Node>,
+    /// The value to substitute it with
+    pub to_value: Option<BigInt>,
+    /// Type of the substituted value
+    pub value_type: Option<Node>,
+}
+
+impl Substitution {
+    /// Convert a raw substitution, releasing its value.
+    pub fn from_raw(mut raw: ada_internal_substitution) -> Self {
+        let res = Self::copy_from_raw(&raw);
+        unsafe { ada_internal_substitution_dec_ref(&raw mut raw) };
+        Exception::log_and_ignore();
+        res
+    }
+
+    /// Convert a raw substitution without releasing it.
+    fn copy_from_raw(raw: &ada_internal_substitution) -> Self {
+        // big integers cannot be shared: copy the value through its decimal representation
+        let value = ManuallyDrop::new(unsafe { BigInt::from_raw(raw.to_value) });
+        let to_value = value
+            .as_ref()
+            .and_then(|value| BigInt::new(&value.to_decimal()).ok());
+        Self {
+            from_decl: node(raw.from_decl),
+            to_value,
+            value_type: node(raw.value_type),
+        }
+    }
+}
+
+macro_rules! from_raw_items {
+    ($($raw:ty => $ty:ty),* $(,)?) => { $(
+        impl FromRawItem<$raw> for $ty {
+            unsafe fn from_raw_item(raw: &$raw) -> Self {
+                // SAFETY: these structures only hold nodes and scalars, which are not
+                // reference-counted
+                <$ty>::from_raw(unsafe { core::ptr::read(raw) })
+            }
+        }
+    )* };
+}
+
+from_raw_items! {
+    ada_internal_ref_result => RefResult,
+    ada_internal_param_actual => ParamActual,
+    ada_internal_param_match => ParamMatch,
+    ada_internal_completion_item => CompletionItem,
+}
+
+impl FromRawItem<ada_internal_discriminant_values> for DiscriminantValues {
+    unsafe fn from_raw_item(raw: &ada_internal_discriminant_values) -> Self {
+        Self::from_raw(raw)
+    }
+}
+
+impl FromRawItem<ada_internal_doc_annotation> for DocAnnotation {
+    unsafe fn from_raw_item(raw: &ada_internal_doc_annotation) -> Self {
+        Self::copy_from_raw(raw)
+    }
+}
+
+impl FromRawItem<ada_internal_substitution> for Substitution {
+    unsafe fn from_raw_item(raw: &ada_internal_substitution) -> Self {
+        Self::copy_from_raw(raw)
+    }
+}
+
+impl FromRawItem<ada_internal_shape> for Shape {
+    unsafe fn from_raw_item(raw: &ada_internal_shape) -> Self {
+        // the array owns the arrays of its items: copy them without releasing anything
+        Self::copy_from_raw(raw)
+    }
+}
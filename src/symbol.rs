@@ -0,0 +1,83 @@
+//! Symbols, i.e. interned identifiers
+
+use std::{fmt::Display, hash::Hash};
+
+use libadalang_sys::{ada_symbol_text, ada_symbol_type};
+
+use crate::text::{self, Text};
+
+/// A symbol interned in an analysis context.
+///
+/// Symbols are canonicalized by libadalang according to Ada rules (e.g. case folding), and two
+/// symbols from the same context are equal if and only if they have the same canonical text.
+/// Equality and hashing only compare the interned values, which makes them cheap.
+///
+/// Symbols from different contexts are never equal.
+#[derive(Clone)]
+pub struct Symbol {
+    raw: ada_symbol_type,
+    text: Box<str>,
+}
+
+impl Symbol {
+    /// Create a symbol from its raw value.
+    ///
+    /// # Safety
+    /// The `raw` value must be a valid symbol, whose context is still alive.
+    pub unsafe fn from_raw(raw: ada_symbol_type) -> Self {
+        let mut text = text::empty_raw();
+        unsafe { ada_symbol_text((&raw const raw).cast_mut(), &raw mut text) };
+        Self {
+            raw,
+            text: text::take_string(text).into(),
+        }
+    }
+
+    /// Get a copy of the inner raw symbol.
+    pub fn as_raw(&self) -> ada_symbol_type {
+        self.raw
+    }
+
+    /// Get the canonical text of this symbol.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Create a `Text` from the canonical text of this symbol.
+    pub fn to_text(&self) -> Text {
+        Text::new(&self.text)
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw.thin_sym == other.raw.thin_sym && self.raw.table == other.raw.table
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.raw.thin_sym.hash(state);
+        self.raw.table.hash(state);
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({:?})", self.text)
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::{fmt::Display, mem::MaybeUninit};
 
-use libadalang_sys::{ada_destroy_text, ada_text, ada_text_from_utf8};
+use libadalang_sys::{ada_destroy_text, ada_string_type, ada_text, ada_text_from_utf8};
 
 use crate::exception::Exception;
 
@@ -68,6 +68,42 @@ impl Text {
     }
 }
 
+/// An empty, non-owning `ada_text`, to be filled by libadalang.
+pub(crate) fn empty_raw() -> ada_text {
+    ada_text {
+        chars: core::ptr::null_mut(),
+        length: 0,
+        is_allocated: 0,
+    }
+}
+
+/// Copy an `ada_text` returned by libadalang into a Rust string, destroying it if it is owning.
+pub(crate) fn take_string(raw: ada_text) -> String {
+    let res = String::from(Text::from_raw_borrow(&raw));
+    // destroy the text if we own it
+    drop(Text::from_raw(raw));
+    res
+}
+
+/// Copy the content of a reference-counted `ada_string_type` into a Rust string, without
+/// releasing it.
+///
+/// # Safety
+/// The `raw` value must be a valid string, or a null pointer.
+pub(crate) unsafe fn string_from_raw(raw: ada_string_type) -> String {
+    if raw.is_null() {
+        return String::new();
+    }
+
+    let len = usize::try_from(unsafe { (*raw).length }).unwrap_or(0);
+    let ptr = unsafe { (&raw const (*raw).content) }.cast::<u32>();
+    let chars = unsafe { core::slice::from_raw_parts(ptr, len) };
+    chars
+        .iter()
+        .map(|&c| char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
 impl AsRef<[char]> for Text {
     fn as_ref(&self) -> &[char] {
         let (ptr, len) = if self.0.chars.is_null() {
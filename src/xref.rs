@@ -0,0 +1,439 @@
+//! Cross-reference index of the units of a project
+//!
+//! An [`XrefIndex`] maps each declaration to its references, for "find all references" and "go to
+//! declaration" queries across a project. Identifiers are resolved with `p_gnat_xref`, which
+//! returns their defining name, with a fallback on `p_referenced_decl`.
+//!
+//! Indexing resolves every identifier, which is slow on large projects: build the index once with
+//! [`XrefIndex::from_project`], save it (with the `json` feature), and update it when files
+//! change with [`XrefIndex::update_unit`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    num::NonZeroUsize,
+};
+
+use crate::{
+    Result,
+    analysis::{Node, Unit, node::BaseKind},
+    diagnostic::{SourceLocation, SourceRange},
+    driver::ProjectAnalyzer,
+    gpr_project::GprProject,
+    introspection::Value,
+};
+
+/// A range of a source file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub filename: String,
+    pub sloc_range: SourceRange,
+}
+
+impl Location {
+    pub fn new(filename: impl Into<String>, sloc_range: SourceRange) -> Self {
+        Self {
+            filename: filename.into(),
+            sloc_range,
+        }
+    }
+
+    /// Return the location of a node, or fail if it is stale.
+    pub fn of_node(node: &Node) -> Result<Self> {
+        Ok(Self::new(node.unit().filename(), node.sloc_range()?))
+    }
+}
+
+impl Display for Location {
+    /// Format the location as `file:line:col`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.filename, self.sloc_range.start)
+    }
+}
+
+/// A reference from a range of an indexed file to a declaration.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Reference {
+    sloc_range: SourceRange,
+    /// Location of the defining name of the declaration
+    decl: Location,
+}
+
+/// The references found in a file.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct FileXrefs {
+    /// Hash of the source text, to detect changes
+    hash: u64,
+    /// References, sorted by range
+    references: Vec<Reference>,
+}
+
+/// Hash the source text of a file, with FNV-1a, which is stable across platforms and versions.
+fn text_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Return the defining name of the declaration that the identifier `id` refers to.
+pub fn resolve(id: &Node) -> Option<Node> {
+    if let Ok(Value::Node(Some(name))) = id.eval("p_gnat_xref", &[]) {
+        return Some(name);
+    }
+    let Ok(Value::Node(Some(decl))) = id.eval("p_referenced_decl", &[]) else {
+        return None;
+    };
+    match decl.eval("p_defining_name", &[]) {
+        Ok(Value::Node(name)) => name,
+        _ => None,
+    }
+}
+
+/// Resolve all the identifiers of `unit`.
+fn index_file(unit: &Unit) -> Result<FileXrefs> {
+    let hash = text_hash(&unit.unparse()?);
+    let filename = unit.filename();
+
+    // filenames of the units of declarations, by address of the unit
+    let mut filenames = HashMap::new();
+    let mut references = Vec::new();
+    for id in unit
+        .root()
+        .into_iter()
+        .map(|root| root.traverse())
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .filter(|node| node.is_a(BaseKind::BASE_ID))
+    {
+        let Some(name) = resolve(&id) else {
+            continue;
+        };
+        let decl_unit = name.unit();
+        let decl_filename = filenames
+            .entry(decl_unit.as_raw() as usize)
+            .or_insert_with(|| decl_unit.filename())
+            .clone();
+
+        let sloc_range = id.sloc_range()?;
+        let decl = Location::new(decl_filename, name.sloc_range()?);
+        // skip defining names, which refer to themselves
+        if decl.filename != filename || !decl.sloc_range.contains_range(&sloc_range) {
+            references.push(Reference { sloc_range, decl });
+        }
+    }
+
+    references.sort();
+    references.dedup();
+    Ok(FileXrefs { hash, references })
+}
+
+/// An index of the references to the declarations of a set of files.
+#[derive(Debug, Clone, Default)]
+pub struct XrefIndex {
+    files: BTreeMap<String, FileXrefs>,
+    /// References of each declaration
+    declarations: HashMap<Location, BTreeSet<Location>>,
+    /// Ranges of the declarations that have references, by file
+    file_declarations: HashMap<String, BTreeSet<SourceRange>>,
+    /// Files that could not be indexed, with the error
+    skipped: BTreeMap<String, String>,
+}
+
+impl XrefIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index the source files of `project`, parsed and resolved on `jobs` threads.
+    ///
+    /// Files that cannot be indexed (e.g. because they cannot be parsed) are skipped, see
+    /// [`XrefIndex::skipped_files`].
+    pub fn from_project(project: GprProject, jobs: NonZeroUsize) -> Result<Self> {
+        Self::from_analyzer(ProjectAnalyzer::new(project).jobs(jobs))
+    }
+
+    /// Index the source files selected by `analyzer`, with its options, e.g. its tab stop.
+    ///
+    /// Files that cannot be indexed are skipped, see [`XrefIndex::from_project`].
+    pub fn from_analyzer(analyzer: ProjectAnalyzer<'_>) -> Result<Self> {
+        let analyses = analyzer.run(|unit| index_file(&unit).map_err(|err| err.to_string()))?;
+
+        let mut res = Self::new();
+        for analysis in analyses {
+            match analysis.result {
+                Ok(Ok(xrefs)) => res.insert(analysis.filename, xrefs),
+                Ok(Err(err)) => {
+                    res.skipped.insert(analysis.filename, err);
+                }
+                Err(err) => {
+                    res.skipped.insert(analysis.filename, err.to_string());
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Iterate over the files that [`XrefIndex::from_project`] could not index, with the
+    /// message of the error. Indexing one of them later removes it.
+    pub fn skipped_files(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.skipped
+            .iter()
+            .map(|(filename, err)| (filename.as_str(), err.as_str()))
+    }
+
+    fn insert(&mut self, filename: String, xrefs: FileXrefs) {
+        self.remove_file(&filename);
+        self.skipped.remove(&filename);
+        for reference in &xrefs.references {
+            self.declarations
+                .entry(reference.decl.clone())
+                .or_default()
+                .insert(Location::new(filename.as_str(), reference.sloc_range));
+            self.file_declarations
+                .entry(reference.decl.filename.clone())
+                .or_default()
+                .insert(reference.decl.sloc_range);
+        }
+        self.files.insert(filename, xrefs);
+    }
+
+    /// Remove the references found in the file `filename` from the index.
+    pub fn remove_file(&mut self, filename: &str) {
+        let Some(xrefs) = self.files.remove(filename) else {
+            return;
+        };
+        for reference in xrefs.references {
+            let location = Location::new(filename, reference.sloc_range);
+            let Some(refs) = self.declarations.get_mut(&reference.decl) else {
+                continue;
+            };
+            refs.remove(&location);
+            if refs.is_empty() {
+                self.declarations.remove(&reference.decl);
+                let decl = &reference.decl;
+                if let Some(decls) = self.file_declarations.get_mut(&decl.filename) {
+                    decls.remove(&decl.sloc_range);
+                    if decls.is_empty() {
+                        self.file_declarations.remove(&decl.filename);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Index the references of `unit`, replacing those previously found in its file.
+    pub fn index_unit(&mut self, unit: &Unit) -> Result<()> {
+        let xrefs = index_file(unit)?;
+        self.insert(unit.filename(), xrefs);
+        Ok(())
+    }
+
+    /// Index the references of `unit` if its source changed since it was indexed, e.g. after a
+    /// reparse. Returns whether the index was updated.
+    ///
+    /// Declarations of the unit may have moved: references to them from other files are only
+    /// updated when those files are, see [`XrefIndex::files_referencing`].
+    pub fn update_unit(&mut self, unit: &Unit) -> Result<bool> {
+        let hash = text_hash(&unit.unparse()?);
+        if self
+            .files
+            .get(&unit.filename())
+            .is_some_and(|xrefs| xrefs.hash == hash)
+        {
+            return Ok(false);
+        }
+        self.index_unit(unit)?;
+        Ok(true)
+    }
+
+    /// Iterate over the indexed files.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Return the indexed files that reference declarations of the file `filename`, sorted.
+    pub fn files_referencing(&self, filename: &str) -> Vec<&str> {
+        let mut res = BTreeSet::new();
+        for &sloc_range in self.file_declarations.get(filename).into_iter().flatten() {
+            let decl = Location::new(filename, sloc_range);
+            res.extend(
+                self.declarations[&decl]
+                    .iter()
+                    .map(|reference| reference.filename.as_str())
+                    .filter(|file| *file != filename),
+            );
+        }
+        res.into_iter().collect()
+    }
+
+    /// Iterate over the declarations that have references, by location of their defining name.
+    pub fn declarations(&self) -> impl Iterator<Item = &Location> {
+        self.declarations.keys()
+    }
+
+    /// Return the references to the declaration whose defining name is at `decl`, sorted.
+    pub fn references(&self, decl: &Location) -> Vec<&Location> {
+        self.declarations
+            .get(decl)
+            .map(|refs| refs.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return the location of the defining name of the declaration referenced at `loc` in the
+    /// file `filename`, if any.
+    pub fn declaration_at(&self, filename: &str, loc: SourceLocation) -> Option<&Location> {
+        let xrefs = self.files.get(filename)?;
+        // references are sorted and do not overlap, so the candidate is the last one starting
+        // before `loc`
+        let idx = xrefs
+            .references
+            .partition_point(|reference| reference.sloc_range.start <= loc);
+        let reference = &xrefs.references[idx.checked_sub(1)?];
+        reference
+            .sloc_range
+            .contains(loc)
+            .then_some(&reference.decl)
+    }
+
+    /// Find all the references to the entity at `loc` in the file `filename`: either a
+    /// reference, or the defining name of a declaration.
+    ///
+    /// Returns the location of the defining name and the references, or `None` if there is no
+    /// known entity at `loc`.
+    pub fn find_references(
+        &self,
+        filename: &str,
+        loc: SourceLocation,
+    ) -> Option<(Location, Vec<&Location>)> {
+        let decl = match self.declaration_at(filename, loc) {
+            Some(decl) => decl.clone(),
+            None => {
+                // defining names do not overlap, so the candidate is the last one starting at or
+                // before `loc`
+                let max = SourceLocation::new(u32::MAX, u16::MAX);
+                let sloc_range = self
+                    .file_declarations
+                    .get(filename)?
+                    .range(..=SourceRange::new(loc, max))
+                    .next_back()
+                    .filter(|sloc_range| sloc_range.contains(loc))?;
+                Location::new(filename, *sloc_range)
+            }
+        };
+        let refs = self.references(&decl);
+        Some((decl, refs))
+    }
+
+    /// Save the index to the file `path`, in JSON.
+    #[cfg(feature = "json")]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, &self.files).map_err(crate::Error::custom)
+    }
+
+    /// Load an index saved with [`XrefIndex::save`].
+    #[cfg(feature = "json")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let files: BTreeMap<String, FileXrefs> =
+            serde_json::from_reader(file).map_err(crate::Error::custom)?;
+
+        let mut res = Self::new();
+        for (filename, xrefs) in files {
+            res.insert(filename, xrefs);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line: u32, start: u16, end: u16) -> SourceRange {
+        SourceRange::new(
+            SourceLocation::new(line, start),
+            SourceLocation::new(line, end),
+        )
+    }
+
+    fn xrefs(references: &[(SourceRange, &str, SourceRange)]) -> FileXrefs {
+        FileXrefs {
+            hash: 0,
+            references: references
+                .iter()
+                .map(|(sloc_range, filename, decl)| Reference {
+                    sloc_range: *sloc_range,
+                    decl: Location::new(*filename, *decl),
+                })
+                .collect(),
+        }
+    }
+
+    fn index() -> XrefIndex {
+        let mut index = XrefIndex::new();
+        index.insert(
+            "a.adb".into(),
+            xrefs(&[
+                (range(3, 4, 6), "a.ads", range(1, 11, 13)),
+                (range(4, 4, 6), "b.ads", range(2, 14, 16)),
+            ]),
+        );
+        index.insert(
+            "b.adb".into(),
+            xrefs(&[(range(5, 1, 3), "b.ads", range(2, 14, 16))]),
+        );
+        index
+    }
+
+    #[test]
+    fn files_referencing() {
+        let index = index();
+        assert_eq!(index.files_referencing("b.ads"), ["a.adb", "b.adb"]);
+        assert_eq!(index.files_referencing("a.ads"), ["a.adb"]);
+        assert!(index.files_referencing("a.adb").is_empty());
+    }
+
+    #[test]
+    fn find_references() {
+        let index = index();
+        let decl = Location::new("b.ads", range(2, 14, 16));
+        let refs = [
+            Location::new("a.adb", range(4, 4, 6)),
+            Location::new("b.adb", range(5, 1, 3)),
+        ];
+
+        // from a reference, and from the defining name
+        for (filename, loc) in [("b.adb", (5, 2)), ("b.ads", (2, 15))] {
+            let (found, found_refs) = index
+                .find_references(filename, SourceLocation::new(loc.0, loc.1))
+                .unwrap();
+            assert_eq!(found, decl);
+            assert_eq!(found_refs, refs.iter().collect::<Vec<_>>());
+        }
+        assert!(
+            index
+                .find_references("b.ads", SourceLocation::new(2, 17))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn remove_file() {
+        let mut index = index();
+        index.remove_file("a.adb");
+        assert!(index.files_referencing("a.ads").is_empty());
+        assert!(
+            index
+                .find_references("a.ads", SourceLocation::new(1, 12))
+                .is_none()
+        );
+        assert_eq!(index.files_referencing("b.ads"), ["b.adb"]);
+        assert_eq!(index.files().collect::<Vec<_>>(), ["b.adb"]);
+    }
+}